@@ -1,10 +1,17 @@
 // library AI for Othello
 
+use std::fmt;
+use std::time::Instant;
+
 // from  bit_lib
-use crate::bit_lib::{can_locate, get_rev_pat}; // bit functions
+use crate::bit_lib::{can_locate, get_rev_pat, get_stable}; // bit functions
+use crate::bit_lib::{get_empty_regions, get_frontier, get_potential_mobility, make_legal_actions};
+use crate::engine::Limits;
 use crate::{Pmove, BOARDSIZE_SQ}; // from main
-const READING_LEVEL: i32 = 8;
-const COMPLETE_READING_LEVEL: usize = 14;
+pub const READING_LEVEL: i32 = 8;
+pub const COMPLETE_READING_LEVEL: usize = 14;
+// 残りがこれ以下では確定石の計算を省く
+const STABILITY_CUT_LEFT: usize = 7;
 // 評価テーブル
 // table 6 - Self made
 #[rustfmt::skip]
@@ -18,11 +25,12 @@ const TABLE6: [i32; BOARDSIZE_SQ] = [
     -24,  -13,   1,   1,   1,   1,  -13, -24,
      50,  -24,   8,   6,   6,   8,  -24,  50,
 ];
-/*
+
 // 評価テーブル
 // table 1 - 隅を重視する
 //#P. Novig: "Paradigms of Artificial Intelligence Programming,"
 //#Case Studies in Common Lisp, Morgan Kaufmann, 1992
+#[rustfmt::skip]
 const TABLE1: [i32; BOARDSIZE_SQ] = [
     120,  -20,  20,   5,   5,  20,  -20, 120,
     -20,  -40,  -5,  -5,  -5,  -5,  -40, -20,
@@ -35,6 +43,7 @@ const TABLE1: [i32; BOARDSIZE_SQ] = [
 ];
 
 // table 2 - 相手に囲ませる 石を多く取らない
+#[rustfmt::skip]
 const TABLE2: [i32; BOARDSIZE_SQ] = [
      30,  -12,   0,  -1,  -1,   0,  -12,  30,
     -12,  -15,  -3,  -3,  -3,  -3,  -15, -12,
@@ -47,6 +56,7 @@ const TABLE2: [i32; BOARDSIZE_SQ] = [
 ];
 
 // table 3 - Stage 1
+#[rustfmt::skip]
 const TABLE3: [i32; BOARDSIZE_SQ] = [
     500,  -86,  96,  26,  26,  96,  -86, 500,
     -86, -1219,  -6,   0,   0,  -6, -1219, -86,
@@ -59,6 +69,7 @@ const TABLE3: [i32; BOARDSIZE_SQ] = [
 ];
 
 // table 4 - Stage 2
+#[rustfmt::skip]
 const TABLE4: [i32; BOARDSIZE_SQ] = [
     500, -240,  85,  69,  69,  85, -240, 500,
    -240, -130,  49,  23,  23,  49, -130, -240,
@@ -70,18 +81,6 @@ const TABLE4: [i32; BOARDSIZE_SQ] = [
     500, -240,  85,  69,  69,  85, -240, 500,
 ];
 
-// table 6 - Self made
-const TABLE6: [i32; BOARDSIZE_SQ] = [
-     50,  -24,   8,   6,   6,   8,  -24,  50,
-    -24,  -13,   1,   1,   1,   1,  -13, -24,
-      8,    1,   0,  -1,  -1,   0,    1,   8,
-      6,    1,  -1,  -1,  -1,  -1,    1,   6,
-      6,    1,  -1,  -1,  -1,  -1,    1,   6,
-      8,    1,   0,  -1,  -1,   0,    1,   8,
-    -24,  -13,   1,   1,   1,   1,  -13, -24,
-     50,  -24,   8,   6,   6,   8,  -24,  50,
-];
-*/
 #[rustfmt::skip]
 const EDGE_DATA1: [u64; 52] = [
     0x0000000000000007,  0x000000000000000f,  0x000000000000001f,
@@ -116,17 +115,181 @@ const MASKS: [u64; 8] = [
     0xfefe_fefe_fefe_fe00,
 ];
 
+// 評価テーブルの選択 Selection of the square table
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SquareTable {
+    Table1,
+    Table2,
+    Table3,
+    Table4,
+    Table6,
+}
+impl SquareTable {
+    fn values(&self) -> &'static [i32; BOARDSIZE_SQ] {
+        match self {
+            SquareTable::Table1 => &TABLE1,
+            SquareTable::Table2 => &TABLE2,
+            SquareTable::Table3 => &TABLE3,
+            SquareTable::Table4 => &TABLE4,
+            SquareTable::Table6 => &TABLE6,
+        }
+    }
+    fn number(&self) -> u32 {
+        match self {
+            SquareTable::Table1 => 1,
+            SquareTable::Table2 => 2,
+            SquareTable::Table3 => 3,
+            SquareTable::Table4 => 4,
+            SquareTable::Table6 => 6,
+        }
+    }
+    fn from_number(n: &str) -> Result<Self, String> {
+        match n {
+            "1" => Ok(SquareTable::Table1),
+            "2" => Ok(SquareTable::Table2),
+            "3" => Ok(SquareTable::Table3),
+            "4" => Ok(SquareTable::Table4),
+            "6" => Ok(SquareTable::Table6),
+            _ => Err(format!("unknown table: {} (1, 2, 3, 4, 6)", n)),
+        }
+    }
+}
+
+// 評価関数の重み 0 で項目を無効にする  Weights of evaluation terms, 0 disables a term
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalProfile {
+    pub table: SquareTable,
+    pub stones: i32,
+    pub square: i32,
+    pub mobility: i32,
+    pub edge: i32,
+    pub open: i32,
+    pub stability: i32,
+    pub potential: i32,
+    pub frontier: i32,
+    pub parity: i32,
+}
+impl Default for EvalProfile {
+    fn default() -> Self {
+        EvalProfile {
+            table: SquareTable::Table6,
+            stones: 2,
+            square: 2,
+            mobility: 2,
+            edge: 2,
+            open: 2,
+            stability: 0,
+            potential: 0,
+            frontier: 0,
+            parity: 0,
+        }
+    }
+}
+impl EvalProfile {
+    // "table=1,stones=2,mobility=off" の形式  Unspecified terms keep the default
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut profile = EvalProfile::default();
+        for item in spec.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            let (key, value) = item
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value: {}", item))?;
+            let (key, value) = (key.trim(), value.trim());
+            if key == "table" {
+                profile.table = SquareTable::from_number(value)?;
+                continue;
+            }
+            let weight = match value {
+                "off" => 0,
+                "on" => *EvalProfile::default().weight_mut(key)?,
+                _ => value
+                    .parse::<i32>()
+                    .map_err(|_| format!("invalid weight: {}", item))?,
+            };
+            *profile.weight_mut(key)? = weight;
+        }
+        Ok(profile)
+    }
+    fn weight_mut(&mut self, key: &str) -> Result<&mut i32, String> {
+        match key {
+            "stones" => Ok(&mut self.stones),
+            "square" => Ok(&mut self.square),
+            "mobility" => Ok(&mut self.mobility),
+            "edge" => Ok(&mut self.edge),
+            "open" => Ok(&mut self.open),
+            "stability" => Ok(&mut self.stability),
+            "potential" => Ok(&mut self.potential),
+            "frontier" => Ok(&mut self.frontier),
+            "parity" => Ok(&mut self.parity),
+            _ => Err(format!("unknown term: {}", key)),
+        }
+    }
+}
+impl fmt::Display for EvalProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "table={},stones={},square={},mobility={},edge={},open={},stability={},\
+             potential={},frontier={},parity={}",
+            self.table.number(),
+            self.stones,
+            self.square,
+            self.mobility,
+            self.edge,
+            self.open,
+            self.stability,
+            self.potential,
+            self.frontier,
+            self.parity
+        )
+    }
+}
+
 // 評価関数の統合 Integration of evaluation functions
-fn eval_all(black: u64, white: u64, left: usize) -> i32 {
+// 終盤 (空き 20 未満) は石差と偶数理論だけ 他の項目は中盤のもの
+// In the endgame (under 20 empties) only the disc count and parity are used, the other terms
+// are for the midgame
+fn eval_all(black: u64, white: u64, left: usize, profile: &EvalProfile) -> i32 {
     if left < 20 {
-        2 * eval_stones(black, white)
-    } else {
-        2 * eval_stones(black, white)
-            + 2 * eval_table(black, white, &TABLE6)
-            + 2 * eval_move(black, white)
-            + 2 * eval_edge(black, white)
-            + 2 * eval_open(black, white)
+        let mut score = profile.stones * eval_stones(black, white);
+        if profile.parity != 0 {
+            score += profile.parity * eval_parity(black, white);
+        }
+        return score;
+    }
+    let mut score = profile.stones * eval_stones(black, white);
+    if profile.square != 0 {
+        score += profile.square * eval_table(black, white, profile.table.values());
+    }
+    if profile.mobility != 0 {
+        score += profile.mobility * eval_move(black, white);
+    }
+    if profile.edge != 0 {
+        score += profile.edge * eval_edge(black, white);
     }
+    if profile.open != 0 {
+        score += profile.open * eval_open(black, white);
+    }
+    if profile.stability != 0 {
+        score += profile.stability * eval_stable(black, white);
+    }
+    if profile.potential != 0 {
+        score += profile.potential * eval_potential(black, white);
+    }
+    if profile.frontier != 0 {
+        score += profile.frontier * eval_frontier(black, white);
+    }
+    if profile.parity != 0 {
+        score += profile.parity * eval_parity(black, white);
+    }
+    score
+}
+
+// 探索の設定と状態  Search settings and state
+struct Search<'a> {
+    profile: &'a EvalProfile,
+    deadline: Option<Instant>,
+    nodes: u64,
+    aborted: bool,
 }
 
 fn alphabeta_s(
@@ -136,11 +299,21 @@ fn alphabeta_s(
     mut alpha: i32,
     beta: i32,
     left: usize,
+    search: &mut Search,
 ) -> (i32, Pmove) {
     let mut action: Pmove = Pmove::Nonenode;
     let mut score: i32;
+    search.nodes += 1;
+    if search.nodes & 0x3ff == 0 {
+        if let Some(deadline) = search.deadline {
+            search.aborted |= Instant::now() >= deadline;
+        }
+    }
+    if search.aborted {
+        return (0, Pmove::Nonenode); // 時間切れ 結果は使わない  Out of time, result is discarded
+    }
     if depth <= 0 {
-        return (eval_all(black, white, left), Pmove::Endnode);
+        return (eval_all(black, white, left, search.profile), Pmove::Endnode);
     }
     let mut can_move_point = can_locate(black, white);
     if can_move_point == 0 {
@@ -148,13 +321,27 @@ fn alphabeta_s(
             return (i32::MIN + 10, Pmove::Endnode); // black is zero, game end
         }
         if can_locate(white, black) == 0 {
-            return (0x1000 * eval_all(black, white, left), Pmove::Endnode); //
+            // 終局は評価プロファイルによらず石差  Game end is scored by discs only
+            return (0x2000 * eval_stones(black, white), Pmove::Endnode);
         } else {
             // pass
-            (score, _) = alphabeta_s(white, black, depth, -beta, -alpha, left);
+            (score, _) = alphabeta_s(white, black, depth, -beta, -alpha, left, search);
         } //# 深さは手数が進まないのだから変わらない
         return (-score, action);
     } else {
+        // 完全読みでは相手の確定石から最終石差の上限が決まる
+        // In the exact search the opponent's stable discs bound the final disc difference
+        // 相手の石が全部確定しても届かない場合は計算しない  Skip when even that cannot cut
+        if depth as usize >= left
+            && left > STABILITY_CUT_LEFT
+            && 0x2000 * (BOARDSIZE_SQ as i32 - 2 * white.count_ones() as i32) <= alpha
+        {
+            let upper =
+                0x2000 * (BOARDSIZE_SQ as i32 - 2 * get_stable(white, black).count_ones() as i32);
+            if upper <= alpha {
+                return (upper, Pmove::Endnode);
+            }
+        }
         while can_move_point != 0 {
             let put_posi = can_move_point & (!can_move_point + 1); //  一番右のビットのみ取り出す
 
@@ -166,6 +353,7 @@ fn alphabeta_s(
                 -beta,
                 -alpha,
                 left - 1,
+                search,
             );
             // when - i32::MIN overflow
             let (_, error_f) = score.overflowing_neg();
@@ -184,13 +372,159 @@ fn alphabeta_s(
     (alpha, action)
 }
 
-pub fn alphabeta(black: u64, white: u64, left: usize) -> Pmove {
-    let depth: i32 = if left < COMPLETE_READING_LEVEL {
-        COMPLETE_READING_LEVEL as i32
+pub fn alphabeta(
+    black: u64,
+    white: u64,
+    left: usize,
+    profile: &EvalProfile,
+    limits: &Limits,
+) -> Pmove {
+    let depth: i32 = if left < limits.exact {
+        limits.exact as i32
+    } else {
+        limits.depth
+    };
+    let mut search = Search {
+        profile,
+        deadline: limits.time.map(|time| Instant::now() + time),
+        nodes: 0,
+        aborted: false,
+    };
+    if search.deadline.is_none() {
+        let depth = full_depth(depth, left);
+        return alphabeta_s(black, white, depth, i32::MIN + 10, i32::MAX - 10, left, &mut search).1;
+    }
+    // 時間制限があるときは反復深化 最後に読み切った深さの手を使う
+    // Iterative deepening under a time limit, the last completed depth gives the move
+    let mut best = Pmove::Nonenode;
+    for d in 1..=depth {
+        let d = full_depth(d, left);
+        let (_, action) =
+            alphabeta_s(black, white, d, i32::MIN + 10, i32::MAX - 10, left, &mut search);
+        if search.aborted {
+            break;
+        }
+        best = action;
+        if d as usize >= left {
+            break; // 終局まで読んだ  Searched to the end of the game
+        }
+    }
+    match best {
+        Pmove::Nonenode => make_legal_actions(black, white)
+            .first()
+            .map_or(Pmove::Passmove, |&mv| Pmove::Pvmove(mv)),
+        _ => best,
+    }
+}
+
+// 読みの深さ 終局まで読むならパスの分も延ばす  The search depth, stretched for passes when
+// it reaches the end.
+// 空きマス数以上の深さは終局までの読み パスで手数が延びても読み切れるように 2 * left まで延ばす
+// A depth of at least the empties is a read to the end; it is stretched to 2 * left so that
+// passes cannot stop the read short of the end
+fn full_depth(depth: i32, left: usize) -> i32 {
+    if depth as usize >= left {
+        depth.max(2 * left as i32)
     } else {
-        READING_LEVEL
+        depth
+    }
+}
+
+// 全ての合法手を読んで評価する 良い順  Score every legal move, best first.
+// 時間制限があるときは全ての手を読み切った最後の深さの評価を使う
+// Under a time limit the scores come from the last depth completed for every move.
+// Returns (scores, depth, nodes); depth >= left means the scores are exact
+pub fn score_moves(
+    black: u64,
+    white: u64,
+    left: usize,
+    profile: &EvalProfile,
+    limits: &Limits,
+) -> (Vec<(u64, i32)>, i32, u64) {
+    let depth: i32 = if left < limits.exact {
+        limits.exact as i32
+    } else {
+        limits.depth
+    };
+    let mut search = Search {
+        profile,
+        deadline: limits.time.map(|time| Instant::now() + time),
+        nodes: 0,
+        aborted: false,
+    };
+    let actions = make_legal_actions(black, white);
+    let first_depth = if search.deadline.is_some() { 1 } else { depth };
+    let mut scores: Vec<(u64, i32)> = actions.iter().map(|&mv| (mv, 0)).collect();
+    let mut completed = 0;
+    for d in first_depth..=depth {
+        let d = full_depth(d, left);
+        let mut current = Vec::new();
+        for &mv in &actions {
+            let rev = get_rev_pat(black, white, mv);
+            let (score, _) = alphabeta_s(
+                white ^ rev,
+                black ^ (mv | rev),
+                d - 1,
+                i32::MIN + 10,
+                i32::MAX - 10,
+                left - 1,
+                &mut search,
+            );
+            current.push((mv, score.saturating_neg()));
+        }
+        if search.aborted {
+            break;
+        }
+        scores = current;
+        completed = d;
+        if d as usize >= left {
+            break; // 終局まで読んだ  Searched to the end of the game
+        }
+    }
+    scores.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    (scores, completed, search.nodes)
+}
+
+// first_move から読みの手順を再探索でたどる 0 はパス
+// Follow the expected line after first_move by searching again at each ply, 0 is a pass
+pub fn principal_variation(
+    black: u64,
+    white: u64,
+    left: usize,
+    first_move: u64,
+    depth: i32,
+    profile: &EvalProfile,
+) -> Vec<u64> {
+    let mut search = Search {
+        profile,
+        deadline: None,
+        nodes: 0,
+        aborted: false,
     };
-    alphabeta_s(black, white, depth, i32::MIN + 10, i32::MAX - 10, left).1
+    let mut pv = vec![first_move];
+    let rev = get_rev_pat(black, white, first_move);
+    let (mut my, mut en) = (white ^ rev, black ^ (first_move | rev));
+    let (mut depth, mut left) = (depth - 1, left - 1);
+    while depth > 0 && left > 0 {
+        if can_locate(my, en) == 0 {
+            if can_locate(en, my) == 0 {
+                break; // 終局  Game end
+            }
+            pv.push(0);
+            (my, en) = (en, my);
+            continue;
+        }
+        let (_, action) =
+            alphabeta_s(my, en, depth, i32::MIN + 10, i32::MAX - 10, left, &mut search);
+        let Pmove::Pvmove(mv) = action else {
+            break;
+        };
+        pv.push(mv);
+        let rev = get_rev_pat(my, en, mv);
+        (my, en) = (en ^ rev, my ^ (mv | rev));
+        (depth, left) = (depth - 1, left - 1);
+    }
+    pv
 }
 
 // 石の枚数の差による評価　Evaluation based on the number of Othello stones
@@ -199,7 +533,7 @@ fn eval_stones(black: u64, white: u64) -> i32 {
 }
 
 // テーブル方式の評価関数 Table-based evaluation function
-fn eval_table(black1: u64, white1: u64, &table: &[i32; 64]) -> i32 {
+fn eval_table(black1: u64, white1: u64, table: &[i32; 64]) -> i32 {
     table
         .iter()
         .enumerate()
@@ -246,6 +580,30 @@ fn eval_edge(black: u64, white: u64) -> i32 {
     black_sum - white_sum
 }
 
+// 確定石の数の差  Difference of stable discs
+fn eval_stable(black: u64, white: u64) -> i32 {
+    get_stable(black, white).count_ones() as i32 - get_stable(white, black).count_ones() as i32
+}
+
+// 潜在的着手可能数の差  Difference of potential mobility
+fn eval_potential(black: u64, white: u64) -> i32 {
+    get_potential_mobility(black, white).count_ones() as i32
+        - get_potential_mobility(white, black).count_ones() as i32
+}
+
+// フロンティアの石は少ない方が良い  Fewer frontier discs is better
+fn eval_frontier(black: u64, white: u64) -> i32 {
+    get_frontier(white, black).count_ones() as i32 - get_frontier(black, white).count_ones() as i32
+}
+
+// 偶数理論 手番側は奇数領域で最後に打てる  The side to move gets the last move in odd regions
+fn eval_parity(black: u64, white: u64) -> i32 {
+    get_empty_regions(black, white)
+        .iter()
+        .map(|region| if region.count_ones() % 2 == 1 { 1 } else { -1 })
+        .sum()
+}
+
 // #開放度 を計算する関数  Function to calculate openness
 fn eval_open(black: u64, white: u64) -> i32 {
     let bw = (black | white) ^ MASK3; // ビット反転、開いているマス目
@@ -264,3 +622,100 @@ fn eval_open(black: u64, white: u64) -> i32 {
     // 開放度は少ない方が良い Less openness is better
     count_sub(white) - count_sub(black)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const BLACK_0: u64 = 0x0000_0008_1000_0000;
+    const WHITE_0: u64 = 0x0000_0010_0800_0000;
+
+    #[test]
+    fn test_eval_profile_parse() {
+        let profile = EvalProfile::parse("table=1, mobility=off,edge=5").unwrap();
+        assert_eq!(profile.table, SquareTable::Table1);
+        assert_eq!(profile.mobility, 0);
+        assert_eq!(profile.edge, 5);
+        assert_eq!(profile.stones, 2);
+        assert_eq!(EvalProfile::parse(&profile.to_string()).unwrap(), profile);
+        assert_eq!(EvalProfile::parse("").unwrap(), EvalProfile::default());
+        assert!(EvalProfile::parse("table=5").is_err());
+        assert!(EvalProfile::parse("corner=1").is_err());
+        let profile = EvalProfile::parse("potential=1,frontier=2,parity=3").unwrap();
+        assert_eq!((profile.potential, profile.frontier, profile.parity), (1, 2, 3));
+    }
+
+    #[test]
+    fn test_eval_profile_terms() {
+        let stones_only = EvalProfile::parse("square=0,mobility=0,edge=0,open=0").unwrap();
+        let black = BLACK_0 | 0x0000_0000_0800_0000;
+        let white = WHITE_0 & !0x0000_0000_0800_0000;
+        assert_eq!(eval_all(black, white, 59, &stones_only), 2 * 2);
+        let default = EvalProfile::default();
+        assert_eq!(
+            eval_all(BLACK_0, WHITE_0, 60, &default),
+            2 * eval_table(BLACK_0, WHITE_0, &TABLE6)
+                + 2 * eval_edge(BLACK_0, WHITE_0)
+                + 2 * eval_open(BLACK_0, WHITE_0)
+        );
+    }
+
+    #[test]
+    fn test_eval_new_terms() {
+        // 初期局面は対称  The initial position is symmetric
+        assert_eq!(eval_potential(BLACK_0, WHITE_0), 0);
+        assert_eq!(eval_frontier(BLACK_0, WHITE_0), 0);
+        assert_eq!(eval_parity(BLACK_0, WHITE_0), -1);
+        let profile = EvalProfile::parse("stones=0,square=0,mobility=0,edge=0,open=0,parity=5").unwrap();
+        assert_eq!(eval_all(BLACK_0, WHITE_0, 60, &profile), -5);
+        // 終盤も偶数理論を使う  Parity also counts in the endgame
+        let (black, white) = (0x6000_1008_1012_0500, 0x8EFC_EFF7_6F6D_3A3F);
+        let parity = eval_parity(black, white);
+        assert_ne!(parity, 0);
+        assert_eq!(eval_all(black, white, 10, &profile), 5 * parity);
+        let frontier = EvalProfile::parse("stones=0,square=0,mobility=0,edge=0,open=0,frontier=5");
+        assert_eq!(eval_all(black, white, 10, &frontier.unwrap()), 0);
+    }
+
+    #[test]
+    fn test_complete_reading() {
+        // main.rs の Test 局面 黒10石勝ち  The Test position is a 10-disc win for Black
+        let black: u64 = 0x6000_1008_1012_0500;
+        let white: u64 = 0x8EFC_EFF7_6F6D_3A3F;
+        let left = BOARDSIZE_SQ - (black | white).count_ones() as usize;
+        let profile = EvalProfile::parse("stability=3").unwrap();
+        let mut search = Search {
+            profile: &profile,
+            deadline: None,
+            nodes: 0,
+            aborted: false,
+        };
+        let (score, _) =
+            alphabeta_s(black, white, 14, i32::MIN + 10, i32::MAX - 10, left, &mut search);
+        assert_eq!(score, 0x2000 * 10);
+    }
+
+    #[test]
+    fn test_score_moves() {
+        let black: u64 = 0x6000_1008_1012_0500;
+        let white: u64 = 0x8EFC_EFF7_6F6D_3A3F;
+        let left = BOARDSIZE_SQ - (black | white).count_ones() as usize;
+        let limits = Limits::new(&crate::engine::Algorithm::AlphaBeta);
+        let (scores, depth, _) =
+            score_moves(black, white, left, &EvalProfile::default(), &limits);
+        assert!(depth as usize >= left);
+        assert_eq!(scores.len(), can_locate(black, white).count_ones() as usize);
+        assert_eq!(scores[0].1, 0x2000 * 10);
+        assert!(scores.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        let best = alphabeta(black, white, left, &EvalProfile::default(), &limits);
+        assert_eq!(best, Pmove::Pvmove(scores[0].0));
+        // 手順を最後まで打つと読み切りの石差になる  Playing out the line gives the exact result
+        let pv = principal_variation(black, white, left, scores[0].0, depth, &EvalProfile::default());
+        let (mut my, mut en) = (black, white);
+        for &mv in &pv {
+            let rev = get_rev_pat(my, en, mv);
+            (my, en) = (en ^ rev, my ^ (mv | rev));
+        }
+        let black_discs = if pv.len().is_multiple_of(2) { my } else { en }.count_ones() as i32;
+        assert_eq!(2 * black_discs - (my | en).count_ones() as i32, 10);
+    }
+}
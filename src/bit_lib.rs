@@ -1,7 +1,7 @@
 // Bit functions for Othello
 
 
-use crate::BOARD_SIZE;
+use crate::{TurnPlayer, BOARD_SIZE};
 use std::collections::HashMap;
 
 #[allow(dead_code)]
@@ -70,6 +70,35 @@ pub fn make_index(mv: u64) -> String {
     format!("{}{}", ab_out_char, n_out_char)
 }
 
+// "f5" -> 着手ビット, "pa" -> 0 (パス)  Inverse of make_index
+pub fn parse_move(text: &str) -> Option<u64> {
+    let text = text.trim().to_ascii_lowercase();
+    if text == "pa" || text == "pass" {
+        return Some(0);
+    }
+    let mut chars = text.chars();
+    let (x, y) = (chars.next()?, chars.next()?);
+    if chars.next().is_some() {
+        return None;
+    }
+    match ("abcdefgh".find(x), "12345678".find(y)) {
+        (Some(x), Some(y)) => Some(0x8000000000000000 >> (x + y * 8)),
+        _ => None,
+    }
+}
+
+// "f5d6c3d3" のような棋譜を着手の列にする  Split a transcript into moves
+pub fn parse_moves(text: &str) -> Option<Vec<u64>> {
+    let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if !chars.len().is_multiple_of(2) {
+        return None;
+    }
+    chars
+        .chunks(2)
+        .map(|pair| parse_move(&pair.iter().collect::<String>()))
+        .collect()
+}
+
 // Convert each bit position on the board using a hash function
 // for De Bruijn sequence m-sequence;maximal length sequence
 //#hashNum = 0x03F566ED27179461
@@ -96,7 +125,9 @@ fn action_to_index(action: u64, table: &HashMap<u64, i32>) -> i32 {
     }
 }
 
-pub fn get_rev_pat(black: u64, white: u64, mv: u64) -> u64 {
+// 1方向ずつ1マスずつ調べる元の実装 検証とベンチマーク用
+// The original square-by-square walk, kept to check and benchmark get_rev_pat
+pub fn get_rev_pat_reference(black: u64, white: u64, mv: u64) -> u64 {
     let mut rev = 0;
     if (black | white) & mv != 0 {
         return rev;
@@ -205,7 +236,9 @@ pub fn get_rev_pat(black: u64, white: u64, mv: u64) -> u64 {
     rev
 }
 
-pub fn can_locate(black: u64, white: u64) -> u64 {
+// 元のシフトの繰り返しの実装 検証とベンチマーク用
+// The original shift cascade, kept to check and benchmark can_locate
+pub fn can_locate_reference(black: u64, white: u64) -> u64 {
     let mut w: u64;
     let mut t: u64;
     let mut blank: u64;
@@ -298,6 +331,340 @@ pub fn can_locate(black: u64, white: u64) -> u64 {
     mobility
 }
 
+// 各マスから8方向に盤端までのマス 前の4方向はビットが増える向き (A1 の側), 後の4方向は減る向き
+// Squares from each square to the edge in 8 directions: the first 4 go towards higher bits
+// (towards A1), the last 4 towards lower bits
+const LINES: [[u64; 8]; 64] = make_lines();
+
+const fn make_lines() -> [[u64; 8]; 64] {
+    // (ビットの列, 行) の増分, 列は H が 0  Steps in (column bit, row), column H is 0
+    const STEPS: [(i32, i32); 8] =
+        [(1, 0), (0, 1), (-1, 1), (1, 1), (-1, 0), (0, -1), (1, -1), (-1, -1)];
+    let mut lines = [[0; 8]; 64];
+    let mut square = 0;
+    while square < 64 {
+        let mut direction = 0;
+        while direction < 8 {
+            let (dx, dy) = STEPS[direction];
+            let (mut x, mut y) = ((square % 8) as i32 + dx, (square / 8) as i32 + dy);
+            while x >= 0 && x < 8 && y >= 0 && y < 8 {
+                lines[square][direction] |= 1 << (x + y * 8);
+                x += dx;
+                y += dy;
+            }
+            direction += 1;
+        }
+        square += 1;
+    }
+    lines
+}
+
+// mv (1マス) に打ったときに返る石 (black が手番側)  Discs flipped when black plays mv
+// 各方向で白石でない最初のマスを見つけ、それが黒石ならその手前までを返す
+// In each direction the first square that is not white closes the run when it is black
+pub fn get_rev_pat(black: u64, white: u64, mv: u64) -> u64 {
+    if mv == 0 || (black | white) & mv != 0 {
+        return 0;
+    }
+    let lines = &LINES[mv.trailing_zeros() as usize];
+    let mut rev = 0;
+    for &line in &lines[..4] {
+        // 最初のマスは最下位のビット  The first square is the lowest bit
+        let blockers = line & !white;
+        let first = blockers & blockers.wrapping_neg();
+        let closed = (first & black != 0) as u64;
+        rev |= line & first.wrapping_sub(1) & closed.wrapping_neg();
+    }
+    for &line in &lines[4..] {
+        // 最初のマスは最上位のビット (なければ H8 で, それは line の外か白石)
+        // The first square is the highest bit, or H8 when there is none, which is then
+        // outside the line or white
+        let blockers = line & !white | 1;
+        let first = 0x8000_0000_0000_0000 >> blockers.leading_zeros();
+        let closed = (first & black & line != 0) as u64;
+        rev |= line & (first.wrapping_neg() ^ first) & closed.wrapping_neg();
+    }
+    rev
+}
+
+// 1組の向きの合法手 白石は盤端を除いた mask の中だけ伸ばすので、折り返しのマスクは要らない
+// 2回目からは2マスずつ伸ばす (Kogge-Stone)
+// Legal moves along one pair of opposite directions. White is masked to the inner squares so
+// the shifts cannot wrap around; after the first steps the fill doubles (Kogge-Stone)
+#[inline(always)]
+fn line_moves(black: u64, white: u64, amount: u32) -> u64 {
+    let mut left = white & (black << amount);
+    left |= white & (left << amount);
+    let pair_left = white & (white << amount);
+    left |= pair_left & (left << (2 * amount));
+    left |= pair_left & (left << (2 * amount));
+    let mut right = white & (black >> amount);
+    right |= white & (right >> amount);
+    let pair_right = white & (white >> amount);
+    right |= pair_right & (right >> (2 * amount));
+    right |= pair_right & (right >> (2 * amount));
+    (left << amount) | (right >> amount)
+}
+
+// 着手可能な位置 (black が手番側)  Legal moves for black
+pub fn can_locate(black: u64, white: u64) -> u64 {
+    let moves = line_moves(black, white & 0x7e7e_7e7e_7e7e_7e7e, 1)
+        | line_moves(black, white & 0x00ff_ffff_ffff_ff00, 8)
+        | line_moves(black, white & 0x007e_7e7e_7e7e_7e00, 7)
+        | line_moves(black, white & 0x007e_7e7e_7e7e_7e00, 9);
+    moves & !(black | white)
+}
+
+// 確定石  Stable discs that can never be flipped for the rest of the game
+// 各方向 (横, 縦, 斜め2方向) について、ラインが埋まっている、盤端にある、
+// または隣が同色の確定石であれば、その方向では返されない
+// 隅から辺、辺から内側へと確定石を伝播させる
+pub fn get_stable(my_pieces: u64, en_pieces: u64) -> u64 {
+    const BORDER: u64 = 0xff81_8181_8181_81ff;
+    let occupied = my_pieces | en_pieces;
+    let horizontal =
+        filled_line(occupied, 1, 0x7f7f7f7f7f7f7f7f, 0xfefefefefefefefe) | 0x8181_8181_8181_8181;
+    let vertical =
+        filled_line(occupied, 8, 0xffffffffffffffff, 0xffffffffffffffff) | 0xff00_0000_0000_00ff;
+    let diagonal7 = filled_line(occupied, 7, 0xfefefefefefefefe, 0x7f7f7f7f7f7f7f7f) | BORDER;
+    let diagonal9 = filled_line(occupied, 9, 0x7f7f7f7f7f7f7f7f, 0xfefefefefefefefe) | BORDER;
+
+    let mut stable: u64 = 0;
+    loop {
+        let h = horizontal
+            | ((stable >> 1) & 0x7f7f7f7f7f7f7f7f)
+            | ((stable << 1) & 0xfefefefefefefefe);
+        let v = vertical | (stable >> 8) | (stable << 8);
+        let d7 = diagonal7
+            | ((stable >> 7) & 0xfefefefefefefefe)
+            | ((stable << 7) & 0x7f7f7f7f7f7f7f7f);
+        let d9 = diagonal9
+            | ((stable >> 9) & 0x7f7f7f7f7f7f7f7f)
+            | ((stable << 9) & 0xfefefefefefefefe);
+        let next = my_pieces & h & v & d7 & d9;
+        if next == stable {
+            return stable;
+        }
+        stable = next;
+    }
+}
+
+// shift 方向のラインがすべて埋まっているマス  Squares whose whole line is filled
+fn filled_line(occupied: u64, shift: u32, mask_r: u64, mask_l: u64) -> u64 {
+    let mut empty = !occupied;
+    for _ in 0..7 {
+        empty |= ((empty >> shift) & mask_r) | ((empty << shift) & mask_l);
+    }
+    !empty
+}
+
+// 8方向の隣接マス  Squares adjacent to any of the bits
+pub fn get_neighbours(bits: u64) -> u64 {
+    ((bits >> 1) & 0x7f7f7f7f7f7f7f7f)
+        | ((bits << 1) & 0xfefefefefefefefe)
+        | (bits >> 8)
+        | (bits << 8)
+        | ((bits >> 7) & 0xfefefefefefefefe)
+        | ((bits << 7) & 0x7f7f7f7f7f7f7f7f)
+        | ((bits >> 9) & 0x7f7f7f7f7f7f7f7f)
+        | ((bits << 9) & 0xfefefefefefefefe)
+}
+
+// 潜在的着手可能点 相手の石に隣接する空きマス  Empty squares next to opponent discs
+pub fn get_potential_mobility(my_pieces: u64, en_pieces: u64) -> u64 {
+    get_neighbours(en_pieces) & !(my_pieces | en_pieces)
+}
+
+// フロンティア 空きマスに隣接する自分の石  Own discs next to an empty square
+pub fn get_frontier(my_pieces: u64, en_pieces: u64) -> u64 {
+    get_neighbours(!(my_pieces | en_pieces)) & my_pieces
+}
+
+// 空きマスの連結領域  Connected regions of empty squares
+pub fn get_empty_regions(my_pieces: u64, en_pieces: u64) -> Vec<u64> {
+    let mut regions = Vec::new();
+    let mut empty = !(my_pieces | en_pieces);
+    while empty != 0 {
+        let mut region = empty & (!empty + 1);
+        loop {
+            let grown = (region | get_neighbours(region)) & empty;
+            if grown == region {
+                break;
+            }
+            region = grown;
+        }
+        regions.push(region);
+        empty ^= region;
+    }
+    regions
+}
+
+// 盤面の8つの対称変換  The 8 symmetries of the board
+// 回転は画面で見た向き (A1 が左上) で時計回り  Rotations are clockwise as displayed, A1 top left
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Transform {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipVertical,     // 上下 (1行目と8行目)  rows 1 and 8 swapped
+    FlipHorizontal,   // 左右 (A列と H列)  columns A and H swapped
+    FlipDiagonal,     // A1-H8 の対角線  about the A1-H8 diagonal
+    FlipAntiDiagonal, // A8-H1 の対角線  about the A8-H1 diagonal
+}
+
+impl Transform {
+    pub const ALL: [Transform; 8] = [
+        Transform::Identity,
+        Transform::Rotate90,
+        Transform::Rotate180,
+        Transform::Rotate270,
+        Transform::FlipVertical,
+        Transform::FlipHorizontal,
+        Transform::FlipDiagonal,
+        Transform::FlipAntiDiagonal,
+    ];
+
+    pub fn apply(self, bits: u64) -> u64 {
+        match self {
+            Transform::Identity => bits,
+            Transform::Rotate90 => flip_horizontal(flip_diagonal(bits)),
+            Transform::Rotate180 => bits.reverse_bits(),
+            Transform::Rotate270 => flip_vertical(flip_diagonal(bits)),
+            Transform::FlipVertical => flip_vertical(bits),
+            Transform::FlipHorizontal => flip_horizontal(bits),
+            Transform::FlipDiagonal => flip_diagonal(bits),
+            Transform::FlipAntiDiagonal => flip_anti_diagonal(bits),
+        }
+    }
+
+    // 元に戻す変換  The transform that undoes this one
+    pub fn inverse(self) -> Self {
+        match self {
+            Transform::Rotate90 => Transform::Rotate270,
+            Transform::Rotate270 => Transform::Rotate90,
+            other => other,
+        }
+    }
+}
+
+pub fn flip_vertical(bits: u64) -> u64 {
+    bits.swap_bytes()
+}
+
+pub fn flip_horizontal(bits: u64) -> u64 {
+    bits.reverse_bits().swap_bytes()
+}
+
+// A1-H8 の対角線で折り返す (転置)  Transpose about the A1-H8 diagonal
+pub fn flip_diagonal(bits: u64) -> u64 {
+    let mut x = bits;
+    let mut t = 0x0f0f_0f0f_0000_0000 & (x ^ (x << 28));
+    x ^= t ^ (t >> 28);
+    t = 0x3333_0000_3333_0000 & (x ^ (x << 14));
+    x ^= t ^ (t >> 14);
+    t = 0x5500_5500_5500_5500 & (x ^ (x << 7));
+    x ^= t ^ (t >> 7);
+    x
+}
+
+// A8-H1 の対角線で折り返す  Reflect about the A8-H1 diagonal
+pub fn flip_anti_diagonal(bits: u64) -> u64 {
+    let mut x = bits;
+    let mut t = x ^ (x << 36);
+    x ^= 0xf0f0_f0f0_0f0f_0f0f & (t ^ (x >> 36));
+    t = 0xcccc_0000_cccc_0000 & (x ^ (x << 18));
+    x ^= t ^ (t >> 18);
+    t = 0xaa00_aa00_aa00_aa00 & (x ^ (x << 9));
+    x ^= t ^ (t >> 9);
+    x
+}
+
+// 8つの対称な局面のうち (my, en) が最小のものと、そこへ移す変換
+// 着手を元の向きに戻すには transform.inverse().apply(mv)
+// The smallest (my, en) among the 8 symmetric positions and the transform that gives it.
+// transform.inverse().apply(mv) maps a move back to the original board
+pub fn canonical(my_pieces: u64, en_pieces: u64) -> (u64, u64, Transform) {
+    let mut best = (my_pieces, en_pieces, Transform::Identity);
+    for transform in &Transform::ALL[1..] {
+        let candidate = (transform.apply(my_pieces), transform.apply(en_pieces));
+        if candidate < (best.0, best.1) {
+            best = (candidate.0, candidate.1, *transform);
+        }
+    }
+    best
+}
+
+// Zobrist ハッシュ: マスと色ごとの乱数の XOR 1行 (8ビット) ごとの表で16回の表引きで求める
+// Zobrist hashing, the XOR of one random key per square and colour, looked up a row byte
+// at a time in 16 lookups
+static ZOBRIST: [[[u64; 256]; 8]; 2] = make_zobrist();
+// 白番のときに XOR する値  XORed in when White is to move
+pub const ZOBRIST_WHITE_TO_MOVE: u64 = 0xd6e8_feb8_6659_fd93;
+
+// 固定の種の splitmix64 で鍵を作るので、実行ごと・プラットフォームごとに同じ値になる
+// Keys come from splitmix64 with a fixed seed, so they are the same on every run and platform
+const fn make_zobrist() -> [[[u64; 256]; 8]; 2] {
+    let mut tables = [[[0; 256]; 8]; 2];
+    let mut state: u64 = 0x4f74_6865_6c6c_6f00;
+    let mut color = 0;
+    while color < 2 {
+        let mut row = 0;
+        while row < 8 {
+            let mut keys = [0; 8];
+            let mut bit = 0;
+            while bit < 8 {
+                state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+                let mut z = state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                keys[bit] = z ^ (z >> 31);
+                bit += 1;
+            }
+            let mut byte = 1;
+            while byte < 256 {
+                // 最下位のビットの鍵と残りのビットの値  Lowest bit's key plus the rest
+                let low = (byte as u32).trailing_zeros() as usize;
+                tables[color][row][byte] = keys[low] ^ tables[color][row][byte & (byte - 1)];
+                byte += 1;
+            }
+            row += 1;
+        }
+        color += 1;
+    }
+    tables
+}
+
+fn zobrist_bits(color: usize, bits: u64) -> u64 {
+    let table = &ZOBRIST[color];
+    let mut key = 0;
+    for (row, keys) in table.iter().enumerate() {
+        key ^= keys[(bits >> (row * 8)) as usize & 0xff];
+    }
+    key
+}
+
+// 局面 (黒石, 白石, 手番) のハッシュ  Hash of a (black, white, side to move) position
+pub fn zobrist(black: u64, white: u64, turn_player: &TurnPlayer) -> u64 {
+    let key = zobrist_bits(0, black) ^ zobrist_bits(1, white);
+    match turn_player {
+        TurnPlayer::Black => key,
+        TurnPlayer::White => key ^ ZOBRIST_WHITE_TO_MOVE,
+    }
+}
+
+// turn_player が mv に打って rev を返した後のハッシュ (パスは mv, rev とも 0)
+// The hash after turn_player plays mv and flips rev, mv and rev are 0 for a pass
+pub fn zobrist_update(key: u64, turn_player: &TurnPlayer, mv: u64, rev: u64) -> u64 {
+    let mover = match turn_player {
+        TurnPlayer::Black => 0,
+        TurnPlayer::White => 1,
+    };
+    // 返った石は両方の色の鍵を入れ替える  Flipped discs swap one colour's keys for the other's
+    let flipped = zobrist_bits(0, rev) ^ zobrist_bits(1, rev);
+    key ^ zobrist_bits(mover, mv) ^ flipped ^ ZOBRIST_WHITE_TO_MOVE
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -309,7 +676,7 @@ mod tests {
         print_typename(print_bits);
         println!("{}", get_function_name(print_bits));        
         assert_eq!(print_typename(print_bits), ());
-        assert_eq!(get_function_name(print_bits), "Othello::bit_lib::print_bits");
+        assert_eq!(get_function_name(print_bits), "othello::bit_lib::print_bits");
     }
 
     #[test]
@@ -323,4 +690,211 @@ mod tests {
         assert_eq!(can_locate(BLACK_0, WHITE_0), 0x1020_0408_0000);
     }
 
-}
\ No newline at end of file
+    // 元の実装と全てのマスで一致するか  Check the fast functions against the originals
+    fn check_move_generation(black: u64, white: u64) {
+        assert_eq!(can_locate(black, white), can_locate_reference(black, white));
+        assert_eq!(get_rev_pat(black, white, 0), 0);
+        for square in 0..64 {
+            let mv = 1 << square;
+            assert_eq!(
+                get_rev_pat(black, white, mv),
+                get_rev_pat_reference(black, white, mv),
+                "{:x} {:x} {}",
+                black,
+                white,
+                make_index(mv)
+            );
+        }
+    }
+
+    #[test]
+    fn test_fast_move_generation() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(1);
+        // いろいろな密度のランダムな盤面  Random boards of every density
+        for _ in 0..5000 {
+            let occupied: u64 = rng.gen::<u64>() & rng.gen::<u64>() | rng.gen::<u64>();
+            let occupied = if rng.gen() { occupied } else { !occupied };
+            let black = occupied & rng.gen::<u64>();
+            check_move_generation(black, occupied ^ black);
+        }
+        // ランダムな対局の局面  Positions from random games
+        for _ in 0..200 {
+            let (mut my, mut en) = (BLACK_0, WHITE_0);
+            loop {
+                check_move_generation(my, en);
+                let actions = make_legal_actions(my, en);
+                if actions.is_empty() {
+                    if can_locate(en, my) == 0 {
+                        break;
+                    }
+                    (my, en) = (en, my);
+                    continue;
+                }
+                let mv = actions[rng.gen_range(0..actions.len())];
+                let rev = get_rev_pat(my, en, mv);
+                (my, en) = (en ^ rev, my ^ (mv | rev));
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_stable() {
+        assert_eq!(get_stable(BLACK_0, WHITE_0), 0);
+        // 全部埋まった盤面はすべて確定石  A full board is entirely stable
+        assert_eq!(get_stable(0xffff_ffff_0000_0000, 0x0000_0000_ffff_ffff), 0xffff_ffff_0000_0000);
+        // A1 から続く辺の石  Edge discs anchored on corner A1
+        let black = 0xe000_0000_0000_0000 | BLACK_0;
+        let white = 0x1000_0000_0000_0000 | WHITE_0;
+        assert_eq!(get_stable(black, white), 0xe000_0000_0000_0000);
+        // A1 から離れた辺の石は確定しない  A lone edge disc is not stable
+        assert_eq!(get_stable(0x2000_0000_0000_0000, 0x0000_0000_0000_0001), 0);
+        // B2 は C1-A3 の斜めが確定しないと確定しない  B2 also needs C1 or A3
+        assert_eq!(get_stable(0xc0c0_0000_0000_0000, WHITE_0), 0xc080_0000_0000_0000);
+        assert_eq!(get_stable(0xe0c0_0000_0000_0000, WHITE_0), 0xe0c0_0000_0000_0000);
+        // D4 は4方向とも埋まったライン上  D4 lies on filled lines only, B8 is empty
+        let d4 = 0x0000_0010_0000_0000;
+        let b8 = 0x0000_0000_0000_0040;
+        assert_eq!(get_stable(d4, !(d4 | b8)), d4);
+        // B8 の横の C8 は確定しない  C8 shares a line with the empty B8
+        let c8 = 0x0000_0000_0000_0020;
+        assert_eq!(get_stable(c8, !(c8 | b8)), 0);
+    }
+
+    #[test]
+    fn test_potential_mobility_frontier() {
+        // 初期局面 白石2つに隣接する空きマス  Empties around the two white discs
+        assert_eq!(get_potential_mobility(BLACK_0, WHITE_0).count_ones(), 10);
+        assert_eq!(get_frontier(BLACK_0, WHITE_0), BLACK_0);
+        assert_eq!(get_frontier(!0, 0), 0);
+    }
+
+    #[test]
+    fn test_get_empty_regions() {
+        assert_eq!(get_empty_regions(BLACK_0, WHITE_0).len(), 1);
+        // H8 と A1 だけが空き  Only A1 and H8 are empty
+        let full = !0x8000_0000_0000_0001u64;
+        let regions = get_empty_regions(full, 0);
+        assert_eq!(regions, vec![0x0000_0000_0000_0001, 0x8000_0000_0000_0000]);
+        // 斜めに繋がる空きは一つの領域  Diagonal neighbours form one region
+        let full = !0x8040_0000_0000_0000u64;
+        assert_eq!(get_empty_regions(full, 0).len(), 1);
+    }
+
+    #[test]
+    fn test_transforms() {
+        // (列, 行) の写像で1マスずつ確かめる  Check every square against (column, row) maps
+        let expected = |transform: Transform, c: usize, r: usize| match transform {
+            Transform::Identity => (c, r),
+            Transform::Rotate90 => (7 - r, c),
+            Transform::Rotate180 => (7 - c, 7 - r),
+            Transform::Rotate270 => (r, 7 - c),
+            Transform::FlipVertical => (c, 7 - r),
+            Transform::FlipHorizontal => (7 - c, r),
+            Transform::FlipDiagonal => (r, c),
+            Transform::FlipAntiDiagonal => (7 - r, 7 - c),
+        };
+        let square = |c: usize, r: usize| 0x8000_0000_0000_0000u64 >> (c + r * 8);
+        for transform in Transform::ALL {
+            for (c, r) in (0..8).flat_map(|c| (0..8).map(move |r| (c, r))) {
+                let (c2, r2) = expected(transform, c, r);
+                assert_eq!(transform.apply(square(c, r)), square(c2, r2), "{:?}", transform);
+                assert_eq!(transform.inverse().apply(transform.apply(square(c, r))), square(c, r));
+            }
+        }
+        assert_eq!(Transform::Rotate90.apply(parse_move("a1").unwrap()), parse_move("h1").unwrap());
+        // 合法手も同じように移る  Legal moves map with the board
+        let (black, white) = (0x0000_1018_0c00_0000, 0x0000_0000_1038_0000);
+        for transform in Transform::ALL {
+            let (b, w) = (transform.apply(black), transform.apply(white));
+            assert_eq!(can_locate(b, w), transform.apply(can_locate(black, white)));
+        }
+    }
+
+    #[test]
+    fn test_canonical() {
+        // 初期局面は4つの変換で変わらない  The initial position has 4 symmetries
+        let (my, en, _) = canonical(BLACK_0, WHITE_0);
+        let same = Transform::ALL
+            .iter()
+            .filter(|t| t.apply(BLACK_0) == BLACK_0 && t.apply(WHITE_0) == WHITE_0);
+        assert_eq!(same.count(), 4);
+        assert!((my, en) <= (BLACK_0, WHITE_0));
+        // 1手目の4つの局面は同じ代表になる  The four first moves share one representative
+        let firsts: Vec<_> = make_legal_actions(BLACK_0, WHITE_0)
+            .into_iter()
+            .map(|mv| {
+                let rev = get_rev_pat(BLACK_0, WHITE_0, mv);
+                canonical(WHITE_0 ^ rev, BLACK_0 ^ (mv | rev))
+            })
+            .collect();
+        assert!(firsts.iter().all(|&(my, en, _)| (my, en) == (firsts[0].0, firsts[0].1)));
+        let (black, white) = (0x0000_1018_0c00_0000, 0x0000_0000_1038_0000);
+        for transform in Transform::ALL {
+            let (b, w) = (transform.apply(black), transform.apply(white));
+            let (my, en, found) = canonical(b, w);
+            assert_eq!((my, en), (canonical(black, white).0, canonical(black, white).1));
+            assert_eq!((found.apply(b), found.apply(w)), (my, en));
+            assert_eq!((found.inverse().apply(my), found.inverse().apply(en)), (b, w));
+        }
+    }
+
+    #[test]
+    fn test_zobrist() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+        use std::collections::HashMap;
+        let start = zobrist(BLACK_0, WHITE_0, &TurnPlayer::Black);
+        assert_ne!(start, zobrist(BLACK_0, WHITE_0, &TurnPlayer::White));
+        assert_ne!(start, zobrist(WHITE_0, BLACK_0, &TurnPlayer::Black));
+        // ランダムな対局で差分の更新と一から求めた値が一致し、違う局面は違う値
+        // Incremental updates match full hashes along random games, and positions do not collide
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut seen: HashMap<u64, (u64, u64, bool)> = HashMap::new();
+        for _ in 0..200 {
+            let (mut black, mut white, mut turn_player) = (BLACK_0, WHITE_0, TurnPlayer::Black);
+            let mut key = start;
+            loop {
+                assert_eq!(key, zobrist(black, white, &turn_player));
+                let position = (black, white, turn_player == TurnPlayer::Black);
+                assert_eq!(*seen.entry(key).or_insert(position), position);
+                let (my, en) = match turn_player {
+                    TurnPlayer::Black => (black, white),
+                    TurnPlayer::White => (white, black),
+                };
+                let actions = make_legal_actions(my, en);
+                if actions.is_empty() && can_locate(en, my) == 0 {
+                    break;
+                }
+                let mv = match actions.is_empty() {
+                    true => 0,
+                    false => actions[rng.gen_range(0..actions.len())],
+                };
+                let rev = get_rev_pat(my, en, mv);
+                key = zobrist_update(key, &turn_player, mv, rev);
+                match turn_player {
+                    TurnPlayer::Black => (black, white) = (black ^ (mv | rev), white ^ rev),
+                    TurnPlayer::White => (black, white) = (black ^ rev, white ^ (mv | rev)),
+                }
+                turn_player = turn_player.flip();
+            }
+        }
+        assert!(seen.len() > 5000);
+    }
+
+    #[test]
+    fn test_parse_move() {
+        for mv in make_legal_actions(BLACK_0, WHITE_0) {
+            assert_eq!(parse_move(&make_index(mv)), Some(mv));
+        }
+        assert_eq!(parse_move("a1"), Some(0x8000_0000_0000_0000));
+        assert_eq!(parse_move("PA"), Some(0));
+        assert_eq!(parse_move("i9"), None);
+        assert_eq!(
+            parse_moves("f5d6 C3"),
+            Some(vec![0x0000_0000_0400_0000, 0x0000_0000_0010_0000, 0x0000_2000_0000_0000])
+        );
+        assert_eq!(parse_moves("f5d"), None);
+    }
+}
@@ -1,7 +1,19 @@
 // board lib for Othello
 
-use crate::bit_lib::{can_locate, get_rev_pat};
-use crate::{Pmove, TurnPlayer, BOARDSIZE_SQ}; // from main // from bit_lib
+use std::time::Duration;
+
+use crate::bit_lib::{can_locate, get_rev_pat, make_index};
+use crate::{Pmove, TurnPlayer, BLACK_0, BOARDSIZE_SQ, WHITE_0}; // from main // from bit_lib
+
+// 着手前の局面と着手  A move and the position it was played from
+#[derive(Clone, Debug)]
+pub struct MoveRecord {
+    pub turn_player: TurnPlayer,
+    pub black: u64,
+    pub white: u64,
+    pub pmove: Pmove,
+    pub time: Duration,
+}
 
 #[derive(Clone, Debug)]
 pub struct Board {
@@ -14,21 +26,29 @@ pub struct Board {
     pub second_player: String,
     step: u32,
     pub left: usize,
+    pub history: Vec<MoveRecord>,
+    redo_list: Vec<MoveRecord>,
 }
 
 impl Board {
     pub fn new(t_player: (TurnPlayer, String, String), black: u64, white: u64) -> Self {
         // turn player black or white, player 1 name, player 2 name
+        let (first, second) = match t_player.0 {
+            TurnPlayer::Black => (black, white),
+            TurnPlayer::White => (white, black),
+        };
         Board {
             turn_player: t_player.0, // turn player black or white
             black,
             white,
-            first: black,
-            second: white,
+            first,
+            second,
             first_player: t_player.1,  // player 1 name
             second_player: t_player.2, // player 2 name
             step: 1,
             left: BOARDSIZE_SQ - (black | white).count_ones() as usize,
+            history: Vec::new(),
+            redo_list: Vec::new(),
         }
     }
 
@@ -38,6 +58,64 @@ impl Board {
     }
 
     pub fn update_board(&mut self, pmove: Pmove) {
+        self.update_board_timed(pmove, Duration::ZERO);
+    }
+
+    // 考慮時間とともに記録する  Record the move with the time spent on it
+    pub fn update_board_timed(&mut self, pmove: Pmove, time: Duration) {
+        self.redo_list.clear();
+        self.play(pmove, time);
+    }
+
+    // 局面を置き換えて履歴を消す  Replace the position and clear the history
+    pub fn reset(&mut self, black: u64, white: u64, turn_player: TurnPlayer) {
+        self.set_position(black, white, turn_player);
+        self.step = 1;
+        self.history.clear();
+        self.redo_list.clear();
+    }
+
+    // 一手戻す 戻した着手を返す  Take back one move and return it
+    pub fn undo(&mut self) -> Option<Pmove> {
+        let record = self.history.pop()?;
+        self.set_position(record.black, record.white, record.turn_player.clone());
+        self.step -= 1;
+        let pmove = record.pmove.clone();
+        self.redo_list.push(record);
+        Some(pmove)
+    }
+
+    // 戻した手を打ち直す  Replay the last undone move
+    pub fn redo(&mut self) -> Option<Pmove> {
+        let record = self.redo_list.pop()?;
+        self.play(record.pmove.clone(), record.time);
+        Some(record.pmove)
+    }
+
+    // 次に打ち直す手  The move redo would replay
+    pub fn next_redo(&self) -> Option<&Pmove> {
+        self.redo_list.last().map(|record| &record.pmove)
+    }
+
+    fn set_position(&mut self, black: u64, white: u64, turn_player: TurnPlayer) {
+        self.black = black;
+        self.white = white;
+        (self.first, self.second) = match turn_player {
+            TurnPlayer::Black => (black, white),
+            TurnPlayer::White => (white, black),
+        };
+        self.turn_player = turn_player;
+        self.left = BOARDSIZE_SQ - (black | white).count_ones() as usize;
+    }
+
+    fn play(&mut self, pmove: Pmove, time: Duration) {
+        self.history.push(MoveRecord {
+            turn_player: self.turn_player.clone(),
+            black: self.black,
+            white: self.white,
+            pmove: pmove.clone(),
+            time,
+        });
         if let Pmove::Pvmove(mv) = pmove {
             let rever_pat = get_rev_pat(self.first, self.second, mv);
             self.put_stone(mv, rever_pat);
@@ -61,32 +139,38 @@ impl Board {
     pub fn print_board(&self) {
         println!();
         println!("----------------------------");
-        println!("   A B C D E F G H");
+        print!("{}", self.board_text());
+    }
+
+    // 盤面と石数の文字列  The board and disc counts as text
+    pub fn board_text(&self) -> String {
+        let mut text = String::from("   A B C D E F G H\n");
         for i in 0..8 {
-            print!("{:2} ", i + 1);
+            text.push_str(&format!("{:2} ", i + 1));
             let b = (self.black >> (56 - i * 8)) & 0xff;
             let w = (self.white >> (56 - i * 8)) & 0xff;
             let bw = b | w;
             for j in (0..8).rev() {
                 if (bw >> j) & 0x01 != 0 {
                     if (b >> j) & 0x01 != 0 {
-                        print!("X ");
+                        text.push_str("X ");
                     } else {
-                        print!("O ");
+                        text.push_str("O ");
                     }
                 } else {
-                    print!(". ");
+                    text.push_str(". ");
                 }
             }
-            println!();
+            text.push('\n');
         }
-        println!(
-            "X{:5}:{:2} vs O{:5}:{:2}",
+        text.push_str(&format!(
+            "X{:5}:{:2} vs O{:5}:{:2}\n",
             self.first_player,
             self.black.count_ones(),
             self.second_player,
             self.white.count_ones()
-        );
+        ));
+        text
     }
 
     fn put_stone(&mut self, put_posi: u64, rev_pat: u64) {
@@ -99,3 +183,72 @@ impl Board {
         }
     }
 }
+
+// 初期局面から着手列を再生する パスは省略してもよい  Replay moves from the start,
+// passes may be omitted. Returns (black, white, turn player)
+pub fn replay_moves(moves: &[u64]) -> Result<(u64, u64, TurnPlayer), String> {
+    let (mut my, mut en, mut turn) = (BLACK_0, WHITE_0, TurnPlayer::Black);
+    for &mv in moves {
+        if can_locate(my, en) == 0 {
+            if can_locate(en, my) == 0 {
+                return Err(format!("game is over before {}", make_index(mv)));
+            }
+            (my, en, turn) = (en, my, turn.flip());
+            if mv == 0 {
+                continue;
+            }
+        }
+        let rev = get_rev_pat(my, en, mv);
+        if rev == 0 {
+            return Err(format!("illegal move {}", make_index(mv)));
+        }
+        (my, en, turn) = (en ^ rev, my ^ (mv | rev), turn.flip());
+    }
+    match turn {
+        TurnPlayer::Black => Ok((my, en, turn)),
+        TurnPlayer::White => Ok((en, my, turn)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bit_lib::parse_moves;
+
+    fn new_board() -> Board {
+        Board::new((TurnPlayer::Black, "X".to_string(), "O".to_string()), BLACK_0, WHITE_0)
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut board = new_board();
+        for mv in parse_moves("f5d6c3").unwrap() {
+            board.update_board(Pmove::Pvmove(mv));
+        }
+        let (black, white) = (board.black, board.white);
+        assert_eq!(board.undo(), Some(Pmove::Pvmove(parse_moves("c3").unwrap()[0])));
+        board.undo();
+        assert_eq!(board.turn_player, TurnPlayer::White);
+        assert_eq!((board.first, board.second), (board.white, board.black));
+        board.undo();
+        assert_eq!((board.black, board.white, board.left), (BLACK_0, WHITE_0, 60));
+        assert_eq!(board.undo(), None);
+        while board.redo().is_some() {}
+        assert_eq!((board.black, board.white), (black, white));
+        assert_eq!(board.turn_player, TurnPlayer::White);
+        // 新しい手で redo は消える  A new move clears the redo list
+        board.undo();
+        board.update_board(Pmove::Pvmove(parse_moves("e3").unwrap()[0]));
+        assert_eq!(board.next_redo(), None);
+    }
+
+    #[test]
+    fn test_undo_pass() {
+        let mut board = new_board();
+        board.update_board(Pmove::Passmove);
+        assert_eq!(board.turn_player, TurnPlayer::White);
+        assert_eq!(board.undo(), Some(Pmove::Passmove));
+        assert_eq!(board.turn_player, TurnPlayer::Black);
+        assert_eq!(board.first, BLACK_0);
+    }
+}
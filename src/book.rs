@@ -0,0 +1,295 @@
+// opening book for Othello
+
+use std::collections::HashMap;
+use std::fs;
+
+use rand::seq::SliceRandom;
+
+// from  bit_lib
+use crate::bit_lib::{can_locate, get_rev_pat, make_index, make_legal_actions, parse_moves};
+use crate::board::replay_moves;
+use crate::engine::Engine;
+use crate::monte_carlo::with_random;
+use crate::{Pmove, TurnPlayer, BLACK_0, BOARDSIZE_SQ, WHITE_0}; // from main
+
+// 作成する定石の手数の既定値  Default number of plies stored when building a book
+const BOOK_DEPTH: usize = 10;
+// 自己対戦で最初にランダムに打つ手数  Random plies at the start of each self-play game
+const RANDOM_PLIES: usize = 4;
+// weighted random で候補にする最善手との差  Score margin for weighted-random choices
+const RANDOM_MARGIN: i32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BookMode {
+    Best,
+    Random,
+    Off,
+}
+
+// 局面 (手番側, 相手) -> 手番側から見た石差  Position (to move, opponent) -> score for the side to move
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Book {
+    entries: HashMap<(u64, u64), i32>,
+}
+
+impl Book {
+    // 1行に "f5d6c3 4" 棋譜と黒から見た石差  One line per position: moves and Black's score
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut book = Book::default();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (moves, score) = line
+                .rsplit_once(char::is_whitespace)
+                .ok_or_else(|| format!("line {}: expected <moves> <score>", n + 1))?;
+            let moves = parse_moves(moves).ok_or_else(|| format!("line {}: bad moves", n + 1))?;
+            let score = score
+                .parse::<i32>()
+                .map_err(|_| format!("line {}: bad score", n + 1))?;
+            let (black, white, turn) =
+                replay_moves(&moves).map_err(|e| format!("line {}: {}", n + 1, e))?;
+            match turn {
+                TurnPlayer::Black => book.entries.insert((black, white), score),
+                TurnPlayer::White => book.entries.insert((white, black), -score),
+            };
+        }
+        Ok(book)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Book::parse(&text)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // 定石にある手のうち手番側の評価で選ぶ  Choose among book moves for the side to move
+    pub fn probe(&self, my_pieces: u64, en_pieces: u64, mode: BookMode) -> Option<u64> {
+        if mode == BookMode::Off {
+            return None;
+        }
+        let mut candidates: Vec<(u64, i32)> = Vec::new();
+        for mv in make_legal_actions(my_pieces, en_pieces) {
+            let rev = get_rev_pat(my_pieces, en_pieces, mv);
+            let child = (en_pieces ^ rev, my_pieces ^ (mv | rev));
+            if let Some(&score) = self.entries.get(&child) {
+                candidates.push((mv, -score));
+            }
+        }
+        let best = candidates.iter().map(|&(_, score)| score).max()?;
+        match mode {
+            BookMode::Off | BookMode::Best => candidates
+                .iter()
+                .find(|&&(_, score)| score == best)
+                .map(|&(mv, _)| mv),
+            BookMode::Random => {
+                let near: Vec<(u64, i32)> = candidates
+                    .into_iter()
+                    .filter(|&(_, score)| score >= best - RANDOM_MARGIN)
+                    .collect();
+                with_random(|rng| {
+                    near.choose_weighted(rng, |&(_, score)| score - (best - RANDOM_MARGIN) + 1)
+                        .ok()
+                        .map(|&(mv, _)| mv)
+                })
+            }
+        }
+    }
+}
+
+// 対局から定石を作る 各局面の値は通過した対局の平均石差 (黒から見た値)
+// 転置した手順は同じ局面として集計し、最初に現れた手順で書く
+// Build a book from games. Each position scores the average result of the games through it;
+// transposed lines count as one position, written with the first line that reached it
+pub fn build_book(games: &[Vec<u64>], depth: usize) -> String {
+    let mut order: Vec<String> = Vec::new();
+    // 局面 (手番側, 相手) -> (order の番号, 石差の合計, 対局数)
+    // Position (to move, opponent) -> (index into order, sum of results, games)
+    let mut results: HashMap<(u64, u64), (usize, i32, i32)> = HashMap::new();
+    for moves in games {
+        let (black, white, _) = match replay_moves(moves) {
+            Ok(position) => position,
+            Err(_) => continue,
+        };
+        if can_locate(black, white) != 0 || can_locate(white, black) != 0 {
+            continue; // 終局していない棋譜は使わない  Skip unfinished games
+        }
+        let score = black.count_ones() as i32 - white.count_ones() as i32;
+        let (mut my, mut en) = (BLACK_0, WHITE_0);
+        let mut line = String::new();
+        for &mv in moves.iter().filter(|&&mv| mv != 0).take(depth) {
+            if can_locate(my, en) == 0 {
+                (my, en) = (en, my); // パス  Pass
+            }
+            let rev = get_rev_pat(my, en, mv);
+            (my, en) = (en ^ rev, my ^ (mv | rev));
+            line.push_str(&make_index(mv).to_ascii_lowercase());
+            let entry = results.entry((my, en)).or_insert_with(|| {
+                order.push(line.clone());
+                (order.len() - 1, 0, 0)
+            });
+            entry.1 += score;
+            entry.2 += 1;
+        }
+    }
+    let mut totals: Vec<(i32, i32)> = vec![(0, 0); order.len()];
+    for &(index, sum, count) in results.values() {
+        totals[index] = (sum, count);
+    }
+    let mut text = String::from("# moves score(black)\n");
+    for (line, (sum, count)) in order.iter().zip(totals) {
+        text.push_str(&format!("{} {}\n", line, (sum as f64 / count as f64).round() as i32));
+    }
+    text
+}
+
+// ランダムな序盤の後に engine 同士で対局する  Engine self-play after random opening plies
+pub fn self_play(engine: &Engine) -> Vec<u64> {
+    let mut moves = Vec::new();
+    let (mut my, mut en) = (BLACK_0, WHITE_0);
+    loop {
+        let legal = make_legal_actions(my, en);
+        if legal.is_empty() {
+            if can_locate(en, my) == 0 {
+                return moves;
+            }
+            moves.push(0);
+            (my, en) = (en, my);
+            continue;
+        }
+        let left = BOARDSIZE_SQ - (my | en).count_ones() as usize;
+        let mv = if moves.len() < RANDOM_PLIES {
+            with_random(|rng| *legal.choose(rng).unwrap())
+        } else {
+            match engine.think(my, en, left) {
+                Pmove::Pvmove(mv) => mv,
+                _ => legal[0],
+            }
+        };
+        let rev = get_rev_pat(my, en, mv);
+        moves.push(mv);
+        (my, en) = (en ^ rev, my ^ (mv | rev));
+    }
+}
+
+// Othello book build <records.txt> <out.txt> [depth]
+// Othello book selfplay <engine> <games> <out.txt> [depth]
+pub fn run_command(args: &[String]) -> Result<(), String> {
+    let usage = "usage: book build <records> <out> [depth]\n       \
+                 book selfplay <engine> <games> <out> [depth]";
+    let parse_depth = |arg: Option<&String>| -> Result<usize, String> {
+        match arg {
+            Some(d) => d.parse().map_err(|_| format!("bad depth: {}", d)),
+            None => Ok(BOOK_DEPTH),
+        }
+    };
+    let (games, out, depth) = match args.first().map(|s| s.as_str()) {
+        Some("build") if args.len() >= 3 => {
+            let text = fs::read_to_string(&args[1]).map_err(|e| format!("{}: {}", args[1], e))?;
+            let games: Vec<Vec<u64>> = text
+                .lines()
+                .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+                .filter_map(|line| {
+                    parse_moves(line)
+                        .or_else(|| parse_moves(line.split_whitespace().next().unwrap_or("")))
+                })
+                .collect();
+            (games, &args[2], parse_depth(args.get(3))?)
+        }
+        Some("selfplay") if args.len() >= 4 => {
+            let engine = Engine::parse(&args[1])?;
+            let count: usize = args[2].parse().map_err(|_| format!("bad games: {}", args[2]))?;
+            let games: Vec<Vec<u64>> = (0..count)
+                .map(|n| {
+                    println!("selfplay {}/{}", n + 1, count);
+                    self_play(&engine)
+                })
+                .collect();
+            (games, &args[3], parse_depth(args.get(4))?)
+        }
+        _ => return Err(usage.to_string()),
+    };
+    let text = build_book(&games, depth);
+    fs::write(out, &text).map_err(|e| format!("{}: {}", out, e))?;
+    println!("{} games -> {} positions: {}", games.len(), text.lines().count() - 1, out);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_book_probe() {
+        // f5 の後 d6 (黒+2) と f6 (黒-6)  After f5, d6 scores +2 and f6 -6 for Black
+        let book = Book::parse("# test\nf5 0\nf5d6 2\nf5f6 -6\n").unwrap();
+        assert_eq!(book.len(), 3);
+        let (black, white, _) = replay_moves(&parse_moves("f5").unwrap()).unwrap();
+        let d6 = parse_moves("d6").unwrap()[0];
+        let f6 = parse_moves("f6").unwrap()[0];
+        assert_eq!(book.probe(white, black, BookMode::Best), Some(f6));
+        assert_eq!(book.probe(white, black, BookMode::Off), None);
+        assert_eq!(book.probe(BLACK_0, WHITE_0, BookMode::Best), parse_moves("f5").map(|m| m[0]));
+        let mv = book.probe(white, black, BookMode::Random).unwrap();
+        assert!(mv == d6 || mv == f6);
+        assert!(Book::parse("f5f5 0").is_err());
+    }
+
+    #[test]
+    fn test_build_book() {
+        let (mut my, mut en) = (BLACK_0, WHITE_0);
+        let mut game = Vec::new();
+        while can_locate(my, en) != 0 || can_locate(en, my) != 0 {
+            let mv = make_legal_actions(my, en).first().copied().unwrap_or(0);
+            let rev = get_rev_pat(my, en, mv);
+            game.push(mv);
+            (my, en) = (en ^ rev, my ^ (mv | rev));
+        }
+        let text = build_book(&[game.clone(), game], 3);
+        let book = Book::parse(&text).unwrap();
+        assert_eq!(book.len(), 3);
+    }
+
+    #[test]
+    fn test_build_book_transpositions() {
+        // f5f6e6 と e6f6f5 は同じ局面 結果の違う2局を1つにまとめる
+        // f5f6e6 and e6f6f5 reach the same position; two games with different results merge
+        let finish = |opening: &str, pick_last: bool| {
+            let mut game = parse_moves(opening).unwrap();
+            let (mut black, mut white, mut turn) = replay_moves(&game).unwrap();
+            loop {
+                let (my, en) = match turn {
+                    TurnPlayer::Black => (black, white),
+                    TurnPlayer::White => (white, black),
+                };
+                if can_locate(my, en) == 0 && can_locate(en, my) == 0 {
+                    return game;
+                }
+                let legal = make_legal_actions(my, en);
+                let mv = if pick_last { legal.last() } else { legal.first() };
+                game.push(mv.copied().unwrap_or(0));
+                (black, white, turn) = replay_moves(&game).unwrap();
+            }
+        };
+        let (a, b) = (finish("f5f6e6", false), finish("e6f6f5", true));
+        let score = |game: &Vec<u64>| {
+            let (black, white, _) = replay_moves(game).unwrap();
+            black.count_ones() as i32 - white.count_ones() as i32
+        };
+        assert_ne!(score(&a), score(&b));
+        let text = build_book(&[a.clone(), b.clone()], 3);
+        let average = ((score(&a) + score(&b)) as f64 / 2.0).round() as i32;
+        assert!(text.contains(&format!("\nf5f6e6 {}\n", average)));
+        assert!(!text.contains("e6f6f5"));
+        // f5, f5f6, f5f6e6, e6, e6f6
+        assert_eq!(Book::parse(&text).unwrap().len(), 5);
+    }
+}
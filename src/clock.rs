@@ -0,0 +1,130 @@
+// game clocks and time controls for Othello
+// 残り時間は盤面の履歴の考慮時間から計算するので、戻す・打ち直す・読み込むと時計も戻る
+// The clocks are computed from the times in the board history, so undo, redo and load
+// move the clocks with the board
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::board::Board;
+use crate::engine::allot_time;
+use crate::TurnPlayer; // from lib
+
+// 時間切れにならないようにエンジンに残す余裕  Safety margin kept back from the engines
+pub(crate) const MARGIN: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TimeControl {
+    PerMove(Duration),               // 1手ごとの制限  A fixed limit for every move
+    SuddenDeath(Duration),           // 切れ負け  One budget for the whole game
+    Increment(Duration, Duration),   // 持ち時間と1手ごとの加算  Base time plus an increment per move
+}
+
+impl TimeControl {
+    // "move:5" 1手5秒, "300" または "sd:300" 切れ負け300秒, "300+5" 300秒と1手5秒加算
+    // "move:5" five seconds per move, "300" or "sd:300" sudden death, "300+5" with an increment
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let seconds = |s: &str| -> Result<Duration, String> {
+            match s.trim().parse::<f64>() {
+                Ok(seconds) if seconds > 0.0 && seconds.is_finite() => {
+                    Ok(Duration::from_secs_f64(seconds))
+                }
+                _ => Err(format!("bad time: {}", s)),
+            }
+        };
+        let text = text.trim();
+        if let Some(time) = text.strip_prefix("move:") {
+            return Ok(TimeControl::PerMove(seconds(time)?));
+        }
+        if let Some((base, increment)) = text.split_once('+') {
+            return Ok(TimeControl::Increment(seconds(base)?, seconds(increment)?));
+        }
+        Ok(TimeControl::SuddenDeath(seconds(text.strip_prefix("sd:").unwrap_or(text))?))
+    }
+
+    // 次の手に使える時間 (1手ごとの制限ではその制限)
+    // The time left for the next move of color (the fixed limit for PerMove)
+    pub fn remaining(&self, board: &Board, color: &TurnPlayer) -> Duration {
+        let times = board
+            .history
+            .iter()
+            .filter(|record| record.turn_player == *color)
+            .map(|record| record.time);
+        match self {
+            TimeControl::PerMove(time) => *time,
+            TimeControl::SuddenDeath(base) => base.saturating_sub(times.sum()),
+            TimeControl::Increment(base, increment) => {
+                times.fold(*base, |remaining, time| remaining.saturating_sub(time) + *increment)
+            }
+        }
+    }
+
+    // 手番側が used を使って時間切れになったか  Whether the side to move lost on time
+    pub fn is_flagged(&self, board: &Board, used: Duration) -> bool {
+        used > self.remaining(board, &board.turn_player)
+    }
+
+    // エンジンの1手の考慮時間  Thinking time for an engine's next move
+    pub fn budget(&self, board: &Board) -> Duration {
+        let remaining = self.remaining(board, &board.turn_player);
+        match self {
+            TimeControl::PerMove(_) => remaining.saturating_sub(MARGIN),
+            TimeControl::SuddenDeath(_) => allot_time(remaining, Duration::ZERO, board.left),
+            TimeControl::Increment(_, increment) => allot_time(remaining, *increment, board.left),
+        }
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeControl::PerMove(time) => write!(f, "{}s per move", time.as_secs_f64()),
+            TimeControl::SuddenDeath(base) => write!(f, "{}s sudden death", base.as_secs_f64()),
+            TimeControl::Increment(base, increment) => {
+                write!(f, "{}s + {}s per move", base.as_secs_f64(), increment.as_secs_f64())
+            }
+        }
+    }
+}
+
+// "04:32.1" のような時計の表示  A clock reading like "04:32.1"
+pub fn format_clock(time: Duration) -> String {
+    let tenths = time.as_millis() / 100;
+    format!("{:02}:{:02}.{}", tenths / 600, tenths / 10 % 60, tenths % 10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bit_lib::parse_move;
+    use crate::position::Position;
+    use crate::Pmove;
+
+    #[test]
+    fn test_time_control() {
+        let start = Position::default();
+        let mut board = Board::new(
+            (start.turn_player, "X".to_string(), "O".to_string()),
+            start.black,
+            start.white,
+        );
+        let increment = TimeControl::parse("60+2").unwrap();
+        assert_eq!(increment, TimeControl::Increment(Duration::from_secs(60), Duration::from_secs(2)));
+        assert_eq!(TimeControl::parse("sd:60"), TimeControl::parse("60"));
+        assert!(TimeControl::parse("move:0").is_err());
+        assert!(TimeControl::parse("fast").is_err());
+
+        board.update_board_timed(Pmove::Pvmove(parse_move("f5").unwrap()), Duration::from_secs(10));
+        board.update_board_timed(Pmove::Pvmove(parse_move("d6").unwrap()), Duration::from_secs(3));
+        let black = &TurnPlayer::Black;
+        assert_eq!(increment.remaining(&board, black), Duration::from_secs(52));
+        let sudden_death = TimeControl::parse("60").unwrap();
+        assert_eq!(sudden_death.remaining(&board, black), Duration::from_secs(50));
+        assert!(sudden_death.is_flagged(&board, Duration::from_secs(51)));
+        assert!(!sudden_death.is_flagged(&board, Duration::from_secs(50)));
+        let per_move = TimeControl::parse("move:5").unwrap();
+        assert_eq!(per_move.budget(&board), Duration::from_millis(4950));
+        assert!(sudden_death.budget(&board) < Duration::from_secs(5));
+        assert_eq!(format_clock(Duration::from_millis(272_150)), "04:32.1");
+    }
+}
@@ -0,0 +1,400 @@
+// engine selection for Othello
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::alpha_beta::{alphabeta, principal_variation, score_moves, EvalProfile};
+use crate::alpha_beta::{COMPLETE_READING_LEVEL, READING_LEVEL};
+use crate::bit_lib::{can_locate, make_index};
+use crate::book::{Book, BookMode};
+use crate::clock::MARGIN;
+use crate::mcts_dag::{mcts_dag, mcts_dag_scores};
+use crate::monte_carlo::{mcts, mcts_scores, primitive_montecarlo, primitive_montecarlo_scores};
+use crate::{Pmove, BOARDSIZE_SQ}; // from main
+
+// MCTS のシミュレーション回数  Simulations per move for MCTS
+const MCTS_PLAYOUTS: u32 = 5000;
+// 原始モンテカルロの1手あたりのロールアウト回数  Rollouts per legal move
+const PRIMITIVE_PLAYOUTS: u32 = 200;
+// 完全読みの値 (石差 x 0x2000) の最大 これを超えるのは全滅の番兵値
+// Largest complete-read score (discs x 0x2000), anything beyond is the wipeout sentinel
+const WIPEOUT_SCORE: i32 = 64 * 0x2000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Algorithm {
+    AlphaBeta,
+    Mcts,
+    PrimitiveMontecarlo,
+    MctsDag, // 転置をまとめる MCTS  MCTS sharing transpositions
+}
+
+// 探索の制限  Search limits
+// depth, exact: alphabeta の読みの深さと完全読みを始める残り手数
+// playouts: MCTS のシミュレーション回数, primitive_montecarlo の1手あたりのロールアウト回数
+// time: 1手の考慮時間 (なければ depth, playouts だけで止める)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Limits {
+    pub depth: i32,
+    pub exact: usize,
+    pub playouts: u32,
+    pub time: Option<Duration>,
+}
+
+impl Limits {
+    pub fn new(algorithm: &Algorithm) -> Self {
+        Limits {
+            depth: READING_LEVEL,
+            exact: COMPLETE_READING_LEVEL,
+            playouts: match algorithm {
+                Algorithm::PrimitiveMontecarlo => PRIMITIVE_PLAYOUTS,
+                _ => MCTS_PLAYOUTS,
+            },
+            time: None,
+        }
+    }
+}
+
+// 手の評価 (手番側から見た値)  A move's score for the side to move
+#[derive(Clone, Debug, PartialEq)]
+pub enum Score {
+    Exact(i32),   // 読み切った最終石差  Final disc difference from a complete read
+    Eval(f64),    // 評価値を石の重みで割ったもの  Evaluation divided by the stones weight
+    WinRate(f64), // モンテカルロの勝率  Monte Carlo win rate, 0.0 to 1.0
+}
+
+impl Score {
+    // 石差の目安  Approximate disc difference
+    pub fn discs(&self) -> f64 {
+        match *self {
+            Score::Exact(discs) => discs as f64,
+            Score::Eval(discs) => discs,
+            Score::WinRate(rate) => (2.0 * rate - 1.0) * BOARDSIZE_SQ as f64,
+        }
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Score::Exact(discs) => write!(f, "{:+}", discs),
+            Score::Eval(discs) => write!(f, "{:+.2}", discs),
+            Score::WinRate(rate) => write!(f, "{:.1}%", rate * 100.0),
+        }
+    }
+}
+
+// 全ての合法手の評価 (良い順), 最善の手順 (0 はパス), 読んだ深さ (モンテカルロは0),
+// 探索したノード数と探索木のバイト数 (MCTS のみ)  Every legal move scored best first, the
+// principal variation (0 is a pass), the depth searched (0 for Monte Carlo), the nodes searched
+// and the bytes held by the search tree (MCTS only, 0 otherwise)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis {
+    pub moves: Vec<(u64, Score)>,
+    pub pv: Vec<u64>,
+    pub depth: i32,
+    pub nodes: u64,
+    pub memory: usize,
+}
+
+impl Analysis {
+    // "F5 D6 PA C3" のような読み筋  The principal variation like "F5 D6 PA C3"
+    pub fn pv_text(&self) -> String {
+        let pv: Vec<String> = self
+            .pv
+            .iter()
+            .map(|&mv| if mv == 0 { "PA".to_string() } else { make_index(mv) })
+            .collect();
+        pv.join(" ")
+    }
+
+    // 1シミュレーションあたりのバイト数  Bytes of search tree per simulation
+    pub fn memory_per_simulation(&self) -> Option<f64> {
+        match (self.memory, self.nodes) {
+            (0, _) | (_, 0) => None,
+            (memory, nodes) => Some(memory as f64 / nodes as f64),
+        }
+    }
+}
+
+// アルゴリズム, 評価プロファイル, 探索の制限と定石
+// Algorithm, evaluation profile, search limits and opening book
+#[derive(Clone, Debug, PartialEq)]
+pub struct Engine {
+    pub algorithm: Algorithm,
+    pub eval: EvalProfile,
+    pub limits: Limits,
+    pub book: Option<Arc<Book>>,
+    pub book_mode: BookMode,
+}
+
+impl Engine {
+    pub fn new(algorithm: Algorithm) -> Self {
+        Engine {
+            limits: Limits::new(&algorithm),
+            algorithm,
+            eval: EvalProfile::default(),
+            book: None,
+            book_mode: BookMode::Best,
+        }
+    }
+
+    // "alphabeta", "mcts", "primitive_montecarlo", "alphabeta:table=1,mobility=off,depth=6",
+    // "mcts:playouts=20000,time=1.5"  (time は秒  time in seconds)
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (name, settings) = match spec.split_once(':') {
+            Some((name, settings)) => (name.trim(), settings),
+            None => (spec.trim(), ""),
+        };
+        let algorithm = match name {
+            "alphabeta" | "alpha_beta" | "ab" => Algorithm::AlphaBeta,
+            "mcts" | "MCTS" => Algorithm::Mcts,
+            "primitive_montecarlo" | "pm" => Algorithm::PrimitiveMontecarlo,
+            "mcts_dag" | "MCTS_DAG" | "dag" => Algorithm::MctsDag,
+            _ => return Err(format!("unknown engine: {}", name)),
+        };
+        let mut engine = Engine::new(algorithm);
+        let mut eval_settings = Vec::new();
+        for item in settings.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            let (key, value) = item.split_once('=').unwrap_or((item, ""));
+            let (key, value) = (key.trim(), value.trim());
+            let bad = || format!("invalid {}: {}", key, value);
+            match key {
+                "depth" => engine.limits.depth = value.parse().map_err(|_| bad())?,
+                "exact" => engine.limits.exact = value.parse().map_err(|_| bad())?,
+                "playouts" => engine.limits.playouts = value.parse().map_err(|_| bad())?,
+                "time" => {
+                    let seconds: f64 = value.parse().map_err(|_| bad())?;
+                    engine.limits.time = Some(Duration::from_secs_f64(seconds.max(0.0)));
+                }
+                _ => eval_settings.push(item),
+            }
+        }
+        engine.eval = EvalProfile::parse(&eval_settings.join(","))?;
+        Ok(engine)
+    }
+
+    // 定石の手  The book move, if the book has this position
+    pub fn book_move(&self, black: u64, white: u64) -> Option<u64> {
+        self.book.as_ref()?.probe(black, white, self.book_mode)
+    }
+
+    pub fn think(&self, black: u64, white: u64, left: usize) -> Pmove {
+        // 探索の前に定石を引く  Probe the book before searching
+        if let Some(mv) = self.book_move(black, white) {
+            return Pmove::Pvmove(mv);
+        }
+        match self.algorithm {
+            Algorithm::AlphaBeta => alphabeta(black, white, left, &self.eval, &self.limits),
+            Algorithm::Mcts => mcts(black, white, left, &self.limits),
+            Algorithm::PrimitiveMontecarlo => {
+                primitive_montecarlo(black, white, left, &self.limits)
+            }
+            Algorithm::MctsDag => mcts_dag(black, white, left, &self.limits),
+        }
+    }
+
+    // 全ての合法手を評価する 定石は使わない  Score every legal move, without the book
+    pub fn analyze(&self, black: u64, white: u64, left: usize) -> Analysis {
+        match self.algorithm {
+            Algorithm::AlphaBeta => {
+                let (scores, depth, nodes) =
+                    score_moves(black, white, left, &self.eval, &self.limits);
+                let exact = depth as usize >= left;
+                let stones = self.eval.stones.max(1) as f64;
+                let moves = scores
+                    .into_iter()
+                    .map(|(mv, score)| {
+                        // 全滅の番兵値は最終石差 ±64  The wipeout sentinel is a final ±64
+                        if score.abs() > WIPEOUT_SCORE {
+                            (mv, Score::Exact(64 * score.signum()))
+                        } else if exact {
+                            (mv, Score::Exact(score / 0x2000))
+                        } else {
+                            (mv, Score::Eval(score as f64 / stones))
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                let pv = match moves.first() {
+                    Some(&(mv, _)) => principal_variation(black, white, left, mv, depth, &self.eval),
+                    None => Vec::new(),
+                };
+                Analysis {
+                    moves,
+                    pv,
+                    depth,
+                    nodes,
+                    memory: 0,
+                }
+            }
+            Algorithm::Mcts | Algorithm::PrimitiveMontecarlo | Algorithm::MctsDag => {
+                // MCTS は訪問回数の多い順  MCTS moves come most visited first
+                let (scores, nodes, pv, memory) = match self.algorithm {
+                    Algorithm::Mcts => mcts_scores(black, white, left, &self.limits),
+                    Algorithm::MctsDag => mcts_dag_scores(black, white, left, &self.limits),
+                    _ => {
+                        let (mut scores, nodes) =
+                            primitive_montecarlo_scores(black, white, left, &self.limits);
+                        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+                        let pv = scores.iter().take(1).map(|&(mv, _)| mv).collect();
+                        (scores, nodes, pv, 0)
+                    }
+                };
+                Analysis {
+                    moves: scores
+                        .into_iter()
+                        .map(|(mv, rate)| (mv, Score::WinRate(rate)))
+                        .collect(),
+                    pv,
+                    depth: 0,
+                    nodes,
+                    memory,
+                }
+            }
+        }
+    }
+
+    // 考慮時間を指定して考える  Think with the given time for this move
+    pub fn think_for(&self, black: u64, white: u64, left: usize, time: Duration) -> Pmove {
+        let mut engine = self.clone();
+        engine.limits.time = Some(time);
+        engine.think(black, white, left)
+    }
+}
+
+// 残り時間から1手の考慮時間を決める  Time for one move from the remaining time
+// 自分の残り手数 (空きマスの半分) と余裕の2手で割り、加算時間を足す
+// Split the remaining time over our remaining moves plus two, then add the increment.
+// A small margin keeps the clock from running out
+pub fn allot_time(remaining: Duration, increment: Duration, left: usize) -> Duration {
+    let moves = (left as u32).div_ceil(2).max(1);
+    let time = remaining / (moves + 2) + increment;
+    time.min(remaining.saturating_sub(MARGIN))
+}
+
+// 合法でない手は最初の合法手に, 打てる手がなければパスにする
+// Replace a move that is not legal with the first legal move, or a pass when there is none
+pub fn legal_move(pmove: Pmove, black: u64, white: u64) -> Pmove {
+    let legal = can_locate(black, white);
+    match pmove {
+        Pmove::Pvmove(mv) if mv.count_ones() == 1 && legal & mv != 0 => pmove,
+        _ if legal == 0 => Pmove::Passmove,
+        _ => Pmove::Pvmove(legal & legal.wrapping_neg()),
+    }
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut settings = Vec::new();
+        if self.algorithm == Algorithm::AlphaBeta && self.eval != EvalProfile::default() {
+            settings.push(self.eval.to_string());
+        }
+        let default = Limits::new(&self.algorithm);
+        if self.algorithm == Algorithm::AlphaBeta {
+            if self.limits.depth != default.depth {
+                settings.push(format!("depth={}", self.limits.depth));
+            }
+            if self.limits.exact != default.exact {
+                settings.push(format!("exact={}", self.limits.exact));
+            }
+        } else if self.limits.playouts != default.playouts {
+            settings.push(format!("playouts={}", self.limits.playouts));
+        }
+        if let Some(time) = self.limits.time {
+            settings.push(format!("time={}", time.as_secs_f64()));
+        }
+        match self.algorithm {
+            Algorithm::AlphaBeta => write!(f, "alphabeta")?,
+            Algorithm::Mcts => write!(f, "MCTS")?,
+            Algorithm::PrimitiveMontecarlo => write!(f, "primitive_montecarlo")?,
+            Algorithm::MctsDag => write!(f, "MCTS_DAG")?,
+        }
+        if !settings.is_empty() {
+            write!(f, ":{}", settings.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engine_parse() {
+        let engine = Engine::parse("alphabeta:table=1,depth=6,time=0.5").unwrap();
+        assert_eq!(engine.limits.depth, 6);
+        assert_eq!(engine.limits.time, Some(Duration::from_millis(500)));
+        assert_eq!(Engine::parse(&engine.to_string()).unwrap(), engine);
+        let engine = Engine::parse("mcts:playouts=100").unwrap();
+        assert_eq!(engine.algorithm, Algorithm::Mcts);
+        assert_eq!(engine.to_string(), "MCTS:playouts=100");
+        let engine = Engine::parse("dag:playouts=100").unwrap();
+        assert_eq!(Engine::parse(&engine.to_string()).unwrap(), engine);
+        assert_eq!(Engine::parse("pm").unwrap().limits.playouts, PRIMITIVE_PLAYOUTS);
+        assert!(Engine::parse("minimax").is_err());
+        assert!(Engine::parse("alphabeta:depth=x").is_err());
+    }
+
+    #[test]
+    fn test_think_with_time() {
+        let engine = Engine::parse("alphabeta:depth=30").unwrap();
+        let black = 0x0000_0008_1000_0000;
+        let white = 0x0000_0010_0800_0000;
+        let pmove = engine.think_for(black, white, 60, Duration::from_millis(50));
+        assert!(matches!(pmove, Pmove::Pvmove(mv) if mv & 0x1020_0408_0000 != 0));
+        // 数マイクロ秒でも合法手を返す  A legal move even with a few microseconds
+        let engine = Engine::parse("mcts").unwrap();
+        let pmove = engine.think_for(black, white, 60, Duration::from_micros(5));
+        assert!(matches!(pmove, Pmove::Pvmove(mv) if mv & 0x1020_0408_0000 != 0));
+    }
+
+    #[test]
+    fn test_analyze() {
+        let black: u64 = 0x6000_1008_1012_0500;
+        let white: u64 = 0x8EFC_EFF7_6F6D_3A3F;
+        let left = 64 - (black | white).count_ones() as usize;
+        let analysis = Engine::new(Algorithm::AlphaBeta).analyze(black, white, left);
+        assert_eq!(analysis.moves[0].1, Score::Exact(10));
+        assert_eq!(analysis.moves[0].1.to_string(), "+10");
+        assert!(analysis.pv_text().starts_with(&make_index(analysis.moves[0].0)));
+        // 時間制限の反復深化でもパスを越えて読み切る  Iterative deepening reads through passes
+        let engine = Engine::parse("alphabeta:time=10").unwrap();
+        assert_eq!(engine.analyze(black, white, left).moves[0].1, Score::Exact(10));
+        let pmove = engine.think(black, white, left);
+        assert_eq!(pmove, Pmove::Pvmove(analysis.moves[0].0));
+        let engine = Engine::parse("mcts:playouts=300").unwrap();
+        let analysis = engine.analyze(0x0000_0008_1000_0000, 0x0000_0010_0800_0000, 60);
+        assert_eq!(analysis.moves.len(), 4);
+        assert!(analysis.moves.iter().all(|(_, score)| matches!(score, Score::WinRate(r)
+            if (0.0..=1.0).contains(r))));
+    }
+
+    #[test]
+    fn test_analyze_wipeout() {
+        // 黒が C1 に打つと白石がなくなる  Black's C1 takes White's last disc
+        let (black, white) = (0x8000_0000_0000_0000, 0x4000_0000_0000_0000);
+        let analysis = Engine::new(Algorithm::AlphaBeta).analyze(black, white, 62);
+        assert_eq!(analysis.moves, vec![(0x2000_0000_0000_0000, Score::Exact(64))]);
+        let engine = Engine::parse("alphabeta:exact=62").unwrap();
+        assert_eq!(engine.analyze(black, white, 62).moves[0].1, Score::Exact(64));
+    }
+
+    #[test]
+    fn test_legal_move() {
+        let (black, white) = (0x0000_0008_1000_0000, 0x0000_0010_0800_0000);
+        let f5 = Pmove::Pvmove(0x1000_0000_0000);
+        assert_eq!(legal_move(f5.clone(), black, white), f5);
+        assert_eq!(legal_move(Pmove::Pvmove(0), black, white), Pmove::Pvmove(0x0008_0000));
+        assert_eq!(legal_move(Pmove::Passmove, black, white), Pmove::Pvmove(0x0008_0000));
+        assert_eq!(legal_move(Pmove::Pvmove(1), 0x8000_0000_0000_0000, 0), Pmove::Passmove);
+    }
+
+    #[test]
+    fn test_allot_time() {
+        let time = allot_time(Duration::from_secs(64), Duration::ZERO, 60);
+        assert_eq!(time, Duration::from_secs(2));
+        let time = allot_time(Duration::from_millis(100), Duration::from_secs(5), 2);
+        assert_eq!(time, Duration::from_millis(50));
+    }
+}
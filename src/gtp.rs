@@ -0,0 +1,361 @@
+// Go Text Protocol (GTP) engine mode for Othello
+
+use std::io;
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant};
+
+// from  bit_lib
+use crate::bit_lib::{can_locate, make_index, parse_move};
+use crate::board::Board;
+use crate::clock::MARGIN;
+use crate::engine::{allot_time, legal_move, Engine};
+use crate::position::Position;
+use crate::{Pmove, TurnPlayer}; // from main
+
+const COMMANDS: [&str; 18] = [
+    "protocol_version",
+    "name",
+    "version",
+    "known_command",
+    "list_commands",
+    "quit",
+    "boardsize",
+    "clear_board",
+    "komi",
+    "play",
+    "genmove",
+    "undo",
+    "final_score",
+    "time_settings",
+    "time_left",
+    "showboard",
+    "engine",
+    "set_engine",
+];
+
+// 残り時間と秒読みの石数  Remaining time and byo-yomi stones
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TimeLeft {
+    time: Duration,
+    stones: u32,
+}
+
+pub struct Gtp {
+    start: Position,
+    board: Board,
+    engine: Engine,
+    time_left: Option<[TimeLeft; 2]>, // black, white
+    // align_turn が入れたパスの棋譜上の位置  History indices of passes inserted by align_turn
+    auto_passes: Vec<usize>,
+}
+
+impl Gtp {
+    pub fn new(engine: Engine, start: Position) -> Self {
+        let board = Board::new(
+            (start.turn_player.clone(), "gtp".to_string(), "gtp".to_string()),
+            start.black,
+            start.white,
+        );
+        Gtp {
+            start,
+            board,
+            engine,
+            time_left: None,
+            auto_passes: Vec::new(),
+        }
+    }
+
+    // 1つのコマンドを実行する  Execute one command, Err is sent as a GTP failure
+    pub fn command(&mut self, name: &str, args: &[&str]) -> Result<String, String> {
+        match (name, args) {
+            ("protocol_version", _) => Ok("2".to_string()),
+            ("name", _) => Ok("Othello_Rust".to_string()),
+            ("version", _) => Ok(env!("CARGO_PKG_VERSION").to_string()),
+            ("known_command", [command, ..]) => Ok(COMMANDS.contains(command).to_string()),
+            ("list_commands", _) => Ok(COMMANDS.join("\n")),
+            ("quit", _) => Ok(String::new()),
+            ("boardsize", [size, ..]) => match *size {
+                "8" => Ok(String::new()),
+                _ => Err("unacceptable size".to_string()),
+            },
+            ("clear_board", _) => {
+                let start = self.start.clone();
+                self.board.reset(start.black, start.white, start.turn_player);
+                self.auto_passes.clear();
+                Ok(String::new())
+            }
+            ("komi", _) => Ok(String::new()),
+            ("play", [color, vertex, ..]) => self.play(parse_color(color)?, vertex),
+            ("genmove", [color, ..]) => self.genmove(parse_color(color)?),
+            ("undo", _) => {
+                self.board.undo().ok_or_else(|| "cannot undo".to_string())?;
+                // 自動で入れたパスなら一緒に戻す 送られてきたパスは残す
+                // Also take back a pass inserted by align_turn, but not one the controller sent
+                if self.auto_passes.last().map(|&index| index + 1) == Some(self.board.history.len())
+                {
+                    self.auto_passes.pop();
+                    self.board.undo();
+                }
+                let len = self.board.history.len();
+                self.auto_passes.retain(|&index| index < len);
+                Ok(String::new())
+            }
+            ("final_score", _) => {
+                let score = self.board.black.count_ones() as i32
+                    - self.board.white.count_ones() as i32;
+                Ok(match score {
+                    0 => "0".to_string(),
+                    _ if score > 0 => format!("B+{}", score),
+                    _ => format!("W+{}", -score),
+                })
+            }
+            ("time_settings", [main_time, byo_yomi_time, byo_yomi_stones, ..]) => {
+                let main_time = parse_seconds(main_time)?;
+                let byo_yomi_time = parse_seconds(byo_yomi_time)?;
+                let stones: u32 = byo_yomi_stones
+                    .parse()
+                    .map_err(|_| "syntax error".to_string())?;
+                // 秒読みの時間が0で石数が0なら時間制限なし  No time limit
+                self.time_left = if byo_yomi_time.is_zero() && stones == 0 && main_time.is_zero() {
+                    None
+                } else if main_time.is_zero() {
+                    let byo_yomi = TimeLeft {
+                        time: byo_yomi_time,
+                        stones,
+                    };
+                    Some([byo_yomi; 2])
+                } else {
+                    let main = TimeLeft {
+                        time: main_time,
+                        stones: 0,
+                    };
+                    Some([main; 2])
+                };
+                Ok(String::new())
+            }
+            ("time_left", [color, time, stones, ..]) => {
+                let time_left = TimeLeft {
+                    time: parse_seconds(time)?,
+                    stones: stones.parse().map_err(|_| "syntax error".to_string())?,
+                };
+                let index = color_index(&parse_color(color)?);
+                let mut times = self.time_left.unwrap_or([time_left; 2]);
+                times[index] = time_left;
+                self.time_left = Some(times);
+                Ok(String::new())
+            }
+            ("showboard", _) => Ok(format!("\n{}", self.board.board_text().trim_end())),
+            ("engine", _) => Ok(self.engine.to_string()),
+            ("set_engine", [spec, ..]) => {
+                let mut engine = Engine::parse(spec)?;
+                engine.book = self.engine.book.clone();
+                engine.book_mode = self.engine.book_mode;
+                self.engine = engine;
+                Ok(String::new())
+            }
+            _ if COMMANDS.contains(&name) => Err("syntax error".to_string()),
+            _ => Err("unknown command".to_string()),
+        }
+    }
+
+    // color の手番にする 相手に打てる手がなければパスを入れる
+    // Make it color's turn, inserting a pass for the other side when it has no move
+    fn align_turn(&mut self, color: &TurnPlayer) -> Result<(), String> {
+        if self.board.turn_player != *color {
+            if can_locate(self.board.first, self.board.second) != 0 {
+                return Err("illegal move".to_string());
+            }
+            self.auto_passes.push(self.board.history.len());
+            self.board.update_board(Pmove::Passmove);
+        }
+        Ok(())
+    }
+
+    fn play(&mut self, color: TurnPlayer, vertex: &str) -> Result<String, String> {
+        let mv = parse_move(vertex).ok_or_else(|| "illegal move".to_string())?;
+        if mv == 0 && self.board.turn_player != color {
+            return Ok(String::new()); // 手番でない側のパス  Pass by the side not to move
+        }
+        self.align_turn(&color)?;
+        let legal = can_locate(self.board.first, self.board.second);
+        if (mv == 0 && legal != 0) || (mv != 0 && legal & mv == 0) {
+            return Err("illegal move".to_string());
+        }
+        self.board.update_board(if mv == 0 { Pmove::Passmove } else { Pmove::Pvmove(mv) });
+        Ok(String::new())
+    }
+
+    fn genmove(&mut self, color: TurnPlayer) -> Result<String, String> {
+        if !self.board.is_can_play() {
+            return Ok("pass".to_string());
+        }
+        self.align_turn(&color)?;
+        if can_locate(self.board.first, self.board.second) == 0 {
+            self.board.update_board(Pmove::Passmove);
+            return Ok("pass".to_string());
+        }
+        let start = Instant::now();
+        let (first, second, left) = (self.board.first, self.board.second, self.board.left);
+        let pmove = match self.time_left.map(|times| times[color_index(&color)]) {
+            Some(time_left) => {
+                let time = if time_left.stones > 0 {
+                    (time_left.time / time_left.stones).saturating_sub(MARGIN)
+                } else {
+                    allot_time(time_left.time, Duration::ZERO, left)
+                };
+                self.engine.think_for(first, second, left, time)
+            }
+            None => self.engine.think(first, second, left),
+        };
+        // 合法でない手は送らない  Never send or record a move that is not legal
+        let pmove = legal_move(pmove, first, second);
+        let elapsed = start.elapsed();
+        if let Some(times) = self.time_left.as_mut() {
+            let time_left = &mut times[color_index(&color)];
+            time_left.time = time_left.time.saturating_sub(elapsed);
+        }
+        match pmove {
+            Pmove::Pvmove(mv) => {
+                self.board.update_board_timed(pmove, elapsed);
+                Ok(make_index(mv))
+            }
+            _ => {
+                self.board.update_board_timed(Pmove::Passmove, elapsed);
+                Ok("pass".to_string())
+            }
+        }
+    }
+}
+
+fn parse_color(color: &str) -> Result<TurnPlayer, String> {
+    match color.to_ascii_lowercase().as_str() {
+        "b" | "black" => Ok(TurnPlayer::Black),
+        "w" | "white" => Ok(TurnPlayer::White),
+        _ => Err("syntax error".to_string()),
+    }
+}
+
+fn color_index(color: &TurnPlayer) -> usize {
+    match color {
+        TurnPlayer::Black => 0,
+        TurnPlayer::White => 1,
+    }
+}
+
+fn parse_seconds(text: &str) -> Result<Duration, String> {
+    text.parse::<f64>()
+        .ok()
+        .filter(|seconds| *seconds >= 0.0)
+        .map(Duration::from_secs_f64)
+        .ok_or_else(|| "syntax error".to_string())
+}
+
+// 標準入出力で GTP を処理する  Serve GTP over stdin and stdout
+pub fn run(engine: Engine, start: Position) {
+    let mut gtp = Gtp::new(engine, start);
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    for line in stdin.lock().lines() {
+        let line = line.expect("Failed to read line");
+        let line = line.split('#').next().unwrap_or("").replace('\t', " ");
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        let id = match words[0].parse::<u32>() {
+            Ok(id) => {
+                words.remove(0);
+                id.to_string()
+            }
+            Err(_) => String::new(),
+        };
+        let Some((&name, args)) = words.split_first() else {
+            continue;
+        };
+        let response = match gtp.command(name, args) {
+            Ok(text) if text.is_empty() => format!("={}\n\n", id),
+            Ok(text) => format!("={} {}\n\n", id, text),
+            Err(e) => format!("?{} {}\n\n", id, e),
+        };
+        stdout.write_all(response.as_bytes()).expect("Failed to write");
+        stdout.flush().expect("Failed to write");
+        if name == "quit" {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_gtp() -> Gtp {
+        Gtp::new(Engine::parse("alphabeta:depth=2").unwrap(), Position::default())
+    }
+
+    #[test]
+    fn test_gtp_play_undo() {
+        let mut gtp = new_gtp();
+        assert_eq!(gtp.command("boardsize", &["8"]), Ok(String::new()));
+        assert!(gtp.command("boardsize", &["19"]).is_err());
+        assert_eq!(gtp.command("play", &["b", "f5"]), Ok(String::new()));
+        assert_eq!(gtp.command("play", &["b", "d6"]), Err("illegal move".to_string()));
+        assert_eq!(gtp.command("play", &["w", "f5"]), Err("illegal move".to_string()));
+        assert_eq!(gtp.command("play", &["w", "d6"]), Ok(String::new()));
+        assert_eq!(gtp.command("final_score", &[]), Ok("0".to_string()));
+        assert_eq!(gtp.command("undo", &[]), Ok(String::new()));
+        assert_eq!(gtp.board.turn_player, TurnPlayer::White);
+        assert_eq!(gtp.command("clear_board", &[]), Ok(String::new()));
+        assert!(gtp.board.history.is_empty());
+        assert!(gtp.command("undo", &[]).is_err());
+        assert_eq!(gtp.command("foo", &[]), Err("unknown command".to_string()));
+        assert_eq!(gtp.command("known_command", &["genmove"]), Ok("true".to_string()));
+    }
+
+    #[test]
+    fn test_gtp_undo_pass() {
+        // 黒 B1 白 A1 黒は打てない  Black B1, White A1 and Black has no move
+        let start = Position::parse("0x4000000000000000 0x8000000000000000 b").unwrap();
+        let mut gtp = Gtp::new(Engine::parse("alphabeta:depth=2").unwrap(), start);
+        // 送られてきたパスは undo で戻さない  An explicit pass stays after undo
+        assert_eq!(gtp.command("play", &["b", "pass"]), Ok(String::new()));
+        assert_eq!(gtp.command("play", &["w", "c1"]), Ok(String::new()));
+        assert_eq!(gtp.command("undo", &[]), Ok(String::new()));
+        assert_eq!(gtp.board.history.len(), 1);
+        assert_eq!(gtp.board.turn_player, TurnPlayer::White);
+        // 自動で入れたパスは一緒に戻す  An inserted pass goes with the undone move
+        assert_eq!(gtp.command("undo", &[]), Ok(String::new()));
+        assert_eq!(gtp.command("play", &["w", "c1"]), Ok(String::new()));
+        assert_eq!(gtp.board.history.len(), 2);
+        assert_eq!(gtp.command("undo", &[]), Ok(String::new()));
+        assert!(gtp.board.history.is_empty());
+        assert_eq!(gtp.board.turn_player, TurnPlayer::Black);
+    }
+
+    #[test]
+    fn test_gtp_genmove() {
+        let mut gtp = new_gtp();
+        assert_eq!(gtp.command("time_settings", &["10", "0", "0"]), Ok(String::new()));
+        assert_eq!(gtp.command("time_left", &["b", "5", "0"]), Ok(String::new()));
+        let vertex = gtp.command("genmove", &["b"]).unwrap();
+        assert!(["C4", "D3", "E6", "F5"].contains(&vertex.as_str()));
+        assert_eq!(gtp.board.turn_player, TurnPlayer::White);
+        assert!(gtp.command("genmove", &["b"]).is_err());
+        assert!(gtp.command("showboard", &[]).unwrap().contains("A B C D E F G H"));
+        // 考慮時間がなくても合法手を打つ  A legal move even with no time to think
+        let mut gtp = Gtp::new(Engine::parse("mcts").unwrap(), Position::default());
+        assert_eq!(gtp.command("time_left", &["b", "0.01", "1"]), Ok(String::new()));
+        let vertex = gtp.command("genmove", &["b"]).unwrap();
+        assert!(["C4", "D3", "E6", "F5"].contains(&vertex.as_str()));
+    }
+
+    #[test]
+    fn test_gtp_pass() {
+        // 白は打てないので黒が続けて打つ  White has no move, Black plays twice
+        let position = Position::parse("0x8000000000000000 0x4000000000000000 b").unwrap();
+        let mut gtp = Gtp::new(Engine::parse("alphabeta:depth=2").unwrap(), position);
+        assert_eq!(gtp.command("genmove", &["b"]), Ok("C1".to_string()));
+        assert_eq!(gtp.command("genmove", &["w"]), Ok("pass".to_string()));
+        assert_eq!(gtp.command("final_score", &[]), Ok("B+3".to_string()));
+    }
+}
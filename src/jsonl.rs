@@ -0,0 +1,438 @@
+// line-delimited JSON engine protocol for Othello
+// 1行に1つの JSON リクエストを読み 1行に1つの JSON レスポンスを返す
+// One JSON request per input line, one JSON response per output line
+
+use std::io;
+use std::io::{BufRead, Write};
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+// from  bit_lib
+use crate::bit_lib::{can_locate, get_rev_pat, make_index, parse_move};
+use crate::engine::{Algorithm, Analysis, Engine, Limits, Score};
+use crate::position::Position;
+use crate::{Pmove, TurnPlayer, BOARDSIZE_SQ}; // from main
+
+// limits.time なしで solve できる空きマスの上限 release ビルドでランダムな対局の局面を
+// 読み切ると 14 空きで 0.6 秒以内, 16 空きでは 50 秒かかるものがある
+// Most empties solved without limits.time. Solving random-game positions in a release
+// build takes under 0.6s at 14 empties, but up to 50s at 16
+pub const SOLVE_MAX_EMPTIES: usize = 14;
+
+// ビットボードは数値か "0x..." の文字列  A bitboard as a number or a "0x..." string
+// JavaScript の数値は 2^53 までしか正確でないので文字列を勧める
+// Strings are recommended since JavaScript numbers are exact only up to 2^53
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Bitboard {
+    Number(u64),
+    Text(String),
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LimitsRequest {
+    depth: Option<i32>,
+    exact: Option<usize>,
+    playouts: Option<u32>,
+    time: Option<f64>, // 秒  seconds
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Request {
+    cmd: String,
+    id: Option<Value>,
+    position: Option<String>,
+    black: Option<Bitboard>,
+    white: Option<Bitboard>,
+    to_move: Option<String>,
+    engine: Option<String>,
+    limits: Option<LimitsRequest>,
+    #[serde(rename = "move")]
+    mv: Option<String>,
+}
+
+impl Request {
+    // position, black/white/to_move のどちらか 省略すると初期局面
+    // Either position or black/white/to_move, the initial position when both are omitted
+    fn position(&self) -> Result<Position, String> {
+        if let Some(text) = &self.position {
+            return Position::parse(text);
+        }
+        let mut position = Position::default();
+        match (&self.black, &self.white) {
+            (Some(black), Some(white)) => {
+                position.black = parse_bitboard(black)?;
+                position.white = parse_bitboard(white)?;
+            }
+            (None, None) => {}
+            _ => return Err("black and white must be given together".to_string()),
+        }
+        if position.black & position.white != 0 {
+            return Err("black and white overlap".to_string());
+        }
+        if let Some(to_move) = &self.to_move {
+            position.turn_player = match to_move.to_ascii_lowercase().as_str() {
+                "black" | "b" | "x" => TurnPlayer::Black,
+                "white" | "w" | "o" => TurnPlayer::White,
+                _ => return Err(format!("bad to_move: {}", to_move)),
+            };
+        }
+        Ok(position)
+    }
+
+    // caps があれば limits をその値までに抑え 時間制限を必ず付ける
+    // With caps the limits are held to them and a time limit is always set
+    fn engine(&self, caps: Option<&Limits>) -> Result<Engine, String> {
+        let mut engine = Engine::parse(self.engine.as_deref().unwrap_or("alphabeta"))?;
+        if let Some(limits) = &self.limits {
+            if let Some(depth) = limits.depth {
+                engine.limits.depth = depth.max(1);
+            }
+            if let Some(exact) = limits.exact {
+                engine.limits.exact = exact;
+            }
+            if let Some(playouts) = limits.playouts {
+                // MCTS は根を展開するのに2回要る  MCTS needs two simulations to expand the root
+                engine.limits.playouts = playouts.max(2);
+            }
+            if let Some(time) = limits.time {
+                engine.limits.time = Some(Duration::from_secs_f64(time.max(0.0)));
+            }
+        }
+        if let Some(caps) = caps {
+            let limits = &mut engine.limits;
+            limits.depth = limits.depth.min(caps.depth);
+            limits.exact = limits.exact.min(caps.exact);
+            limits.playouts = limits.playouts.min(caps.playouts);
+            limits.time = match (limits.time, caps.time) {
+                (Some(time), Some(cap)) => Some(time.min(cap)),
+                (time, cap) => time.or(cap),
+            };
+        }
+        Ok(engine)
+    }
+}
+
+fn parse_bitboard(bitboard: &Bitboard) -> Result<u64, String> {
+    match bitboard {
+        Bitboard::Number(bits) => Ok(*bits),
+        Bitboard::Text(text) => {
+            let digits = text.trim().replace('_', "");
+            let parsed = match digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+                Some(hex) => u64::from_str_radix(hex, 16),
+                None => digits.parse(),
+            };
+            parsed.map_err(|_| format!("bad bitboard: {}", text))
+        }
+    }
+}
+
+fn hex(bits: u64) -> String {
+    format!("0x{:016x}", bits)
+}
+
+fn square(mv: u64) -> String {
+    match mv {
+        0 => "PA".to_string(),
+        _ => make_index(mv),
+    }
+}
+
+// 立っているビットのマス (下位ビットから)  Squares of the set bits, lowest bit first
+fn squares(mut bits: u64) -> Vec<String> {
+    let mut list = Vec::new();
+    while bits != 0 {
+        let bit = bits & (!bits + 1);
+        list.push(make_index(bit));
+        bits ^= bit;
+    }
+    list
+}
+
+fn to_move_name(turn_player: &TurnPlayer) -> &'static str {
+    match turn_player {
+        TurnPlayer::Black => "black",
+        TurnPlayer::White => "white",
+    }
+}
+
+fn position_json(position: &Position) -> Value {
+    json!({
+        "black": hex(position.black),
+        "white": hex(position.white),
+        "to_move": to_move_name(&position.turn_player),
+    })
+}
+
+fn score_json(score: &Score) -> Value {
+    match *score {
+        Score::Exact(discs) => json!({"type": "exact", "value": discs}),
+        Score::Eval(discs) => json!({"type": "eval", "value": discs}),
+        Score::WinRate(rate) => json!({"type": "win_rate", "value": rate}),
+    }
+}
+
+fn analysis_json(analysis: &Analysis) -> Value {
+    let moves: Vec<Value> = analysis
+        .moves
+        .iter()
+        .map(|(mv, score)| json!({"move": square(*mv), "score": score_json(score)}))
+        .collect();
+    json!({
+        "best_move": analysis.moves.first().map(|&(mv, _)| square(mv)),
+        "score": analysis.moves.first().map(|(_, score)| score_json(score)),
+        "pv": analysis.pv.iter().map(|&mv| square(mv)).collect::<Vec<_>>(),
+        "depth": analysis.depth,
+        "nodes": analysis.nodes,
+        "memory": analysis.memory,
+        "moves": moves,
+    })
+}
+
+// 手番側と相手の石  (side to move, opponent)
+fn sides(position: &Position) -> (u64, u64) {
+    match position.turn_player {
+        TurnPlayer::Black => (position.black, position.white),
+        TurnPlayer::White => (position.white, position.black),
+    }
+}
+
+fn analyze(position: &Position, engine: &Engine) -> Result<Value, String> {
+    let (first, second) = sides(position);
+    let left = BOARDSIZE_SQ - (first | second).count_ones() as usize;
+    if can_locate(first, second) == 0 {
+        if can_locate(second, first) == 0 {
+            return Err("game is over".to_string());
+        }
+        // パスしかない  The only move is a pass
+        return Ok(json!({
+            "best_move": "PA",
+            "score": null,
+            "pv": ["PA"],
+            "depth": 0,
+            "nodes": 0,
+            "memory": 0,
+            "moves": [],
+        }));
+    }
+    Ok(analysis_json(&engine.analyze(first, second, left)))
+}
+
+fn handle_request(request: &Request, caps: Option<&Limits>) -> Result<Value, String> {
+    let position = request.position()?;
+    let (first, second) = sides(&position);
+    match request.cmd.as_str() {
+        "legal_moves" => {
+            let moves = can_locate(first, second);
+            Ok(json!({
+                "moves": squares(moves),
+                "must_pass": moves == 0 && can_locate(second, first) != 0,
+                "game_over": moves == 0 && can_locate(second, first) == 0,
+            }))
+        }
+        "apply_move" => {
+            let text = request.mv.as_deref().ok_or("missing move")?;
+            let mv = parse_move(text).ok_or_else(|| format!("bad move: {}", text))?;
+            let legal = can_locate(first, second);
+            if (mv == 0 && (legal != 0 || can_locate(second, first) == 0))
+                || (mv != 0 && legal & mv == 0)
+            {
+                return Err(format!("illegal move: {}", text));
+            }
+            let flipped = get_rev_pat(first, second, mv);
+            let (next_first, next_second) = (second ^ flipped, first ^ (mv | flipped));
+            let next = Position {
+                black: if position.turn_player == TurnPlayer::Black { next_second } else { next_first },
+                white: if position.turn_player == TurnPlayer::Black { next_first } else { next_second },
+                turn_player: position.turn_player.flip(),
+            };
+            let mut response = position_json(&next);
+            response["flipped"] = json!(squares(flipped));
+            Ok(response)
+        }
+        "move" => {
+            // 定石と時間制限を使って1手選ぶ  Pick one move as in a game
+            let engine = request.engine(caps)?;
+            if can_locate(first, second) == 0 && can_locate(second, first) == 0 {
+                return Err("game is over".to_string());
+            }
+            let left = BOARDSIZE_SQ - (first | second).count_ones() as usize;
+            let mv = match engine.think(first, second, left) {
+                Pmove::Pvmove(mv) => mv,
+                _ => 0,
+            };
+            Ok(json!({"move": square(mv)}))
+        }
+        "analyze" => analyze(&position, &request.engine(caps)?),
+        "solve" => {
+            // 終局まで読み切る  Read to the end of the game
+            let mut engine = request.engine(caps)?;
+            if engine.algorithm != Algorithm::AlphaBeta {
+                return Err("solve needs the alphabeta engine".to_string());
+            }
+            let left = BOARDSIZE_SQ - (first | second).count_ones() as usize;
+            // caps.exact を超える局面は読み切りを約束できないので断る
+            // Positions above caps.exact cannot promise an exact solve and are refused
+            if let Some(caps) = caps.filter(|caps| left > caps.exact) {
+                return Err(format!("solve is limited to {} empties: {}", caps.exact, left));
+            }
+            if left > SOLVE_MAX_EMPTIES && engine.limits.time.is_none() {
+                return Err(format!(
+                    "solve needs limits.time above {} empties: {}",
+                    SOLVE_MAX_EMPTIES, left
+                ));
+            }
+            engine.limits.exact = BOARDSIZE_SQ;
+            // 時間内に読み切れなければ solved は false で評価値を返す
+            // solved is false when the time ran out and the scores are evaluations
+            let mut response = analyze(&position, &engine)?;
+            response["solved"] = json!(response["score"]["type"] == "exact");
+            Ok(response)
+        }
+        cmd => Err(format!("unknown cmd: {}", cmd)),
+    }
+}
+
+// リクエストを処理してレスポンスを返す caps はエンジンの limits の上限
+// Handle one request object, return the response object. caps bound the engine limits
+pub fn handle_value(request: Value, caps: Option<&Limits>) -> Value {
+    let request: Request = match serde_json::from_value(request) {
+        Ok(request) => request,
+        Err(e) => return json!({"ok": false, "error": e.to_string()}),
+    };
+    let mut response = match handle_request(&request, caps) {
+        Ok(mut response) => {
+            response["ok"] = json!(true);
+            response
+        }
+        Err(e) => json!({"ok": false, "error": e}),
+    };
+    if let Some(id) = &request.id {
+        response["id"] = id.clone();
+    }
+    response
+}
+
+// 1行のリクエストを処理して1行のレスポンスを返す  Handle one request line, return one response line
+pub fn handle(line: &str) -> String {
+    match serde_json::from_str(line) {
+        Ok(request) => handle_value(request, None).to_string(),
+        Err(e) => json!({"ok": false, "error": e.to_string()}).to_string(),
+    }
+}
+
+// 標準入出力で JSON lines を処理する  Serve JSON lines over stdin and stdout
+pub fn run() {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    for line in stdin.lock().lines() {
+        let line = line.expect("Failed to read line");
+        if line.trim().is_empty() {
+            continue;
+        }
+        writeln!(stdout, "{}", handle(&line)).expect("Failed to write");
+        stdout.flush().expect("Failed to write");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(line: &str) -> Value {
+        serde_json::from_str(&handle(line)).unwrap()
+    }
+
+    #[test]
+    fn test_legal_and_apply() {
+        let response = request(r#"{"cmd":"legal_moves","id":1}"#);
+        assert_eq!(response["ok"], true);
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["moves"], json!(["E6", "F5", "C4", "D3"]));
+        let response = request(
+            r#"{"cmd":"apply_move","black":"0x0000000810000000","white":68853694464,"move":"f5"}"#,
+        );
+        assert_eq!(response["to_move"], "white");
+        assert_eq!(response["black"], "0x000000081c000000");
+        assert_eq!(response["flipped"], json!(["E5"]));
+        let response = request(r#"{"cmd":"apply_move","move":"a1"}"#);
+        assert_eq!(response["ok"], false);
+        assert_eq!(response["error"], "illegal move: a1");
+        assert_eq!(request(r#"{"cmd":"foo"}"#)["ok"], false);
+        assert_eq!(request("not json")["ok"], false);
+    }
+
+    #[test]
+    fn test_analyze_and_solve() {
+        // main.rs の Test 局面 黒10石勝ち  The Test position is a 10-disc win for Black
+        let position = r#""black":"0x6000100810120500","white":"0x8EFCEFF76F6D3A3F""#;
+        let response = request(&format!(r#"{{"cmd":"solve",{}}}"#, position));
+        assert_eq!(response["score"], json!({"type": "exact", "value": 10}));
+        assert_eq!(response["pv"][0], response["best_move"]);
+        assert!(response["nodes"].as_u64().unwrap() > 0);
+        assert_eq!(response["solved"], true);
+        // 空きが多い局面は時間制限が要る 読み切れなければ solved は false
+        // Many empties need a time limit, and solved is false when the read does not finish
+        let response = request(r#"{"cmd":"solve","position":"f5"}"#);
+        assert_eq!(response["error"], "solve needs limits.time above 14 empties: 59");
+        let response = request(r#"{"cmd":"solve","position":"f5","limits":{"time":0.05}}"#);
+        assert_eq!(response["ok"], true);
+        assert_eq!(response["solved"], false);
+        assert_eq!(response["score"]["type"], "eval");
+        let response = request(
+            r#"{"cmd":"analyze","engine":"mcts","limits":{"playouts":200},"position":"f5"}"#,
+        );
+        assert_eq!(response["moves"].as_array().unwrap().len(), 3);
+        assert_eq!(response["score"]["type"], "win_rate");
+        let response = request(r#"{"cmd":"move","engine":"alphabeta:depth=2","position":"f5"}"#);
+        assert!(["D6", "F4", "F6"].contains(&response["move"].as_str().unwrap()));
+        for engine in ["mcts", "mcts_dag"] {
+            let response = request(&format!(
+                r#"{{"cmd":"move","engine":"{}","limits":{{"playouts":0}},"position":"f5"}}"#,
+                engine
+            ));
+            assert!(["D6", "F4", "F6"].contains(&response["move"].as_str().unwrap()));
+        }
+        let response = request(r#"{"cmd":"analyze","limits":{"width":3}}"#);
+        assert_eq!(response["ok"], false);
+    }
+
+    #[test]
+    fn test_solve_at_max_empties() {
+        // ランダムな対局の14空きの局面 黒8石勝ち  A 14-empty random-game position, Black wins by 8
+        let line = r#"{"cmd":"solve","black":"0x00a444b4c494344f","white":"0xe818394a3b6ac980"}"#;
+        let position: Request = serde_json::from_str(line).unwrap();
+        let position = position.position().unwrap();
+        let empties = BOARDSIZE_SQ - (position.black | position.white).count_ones() as usize;
+        assert_eq!(empties, SOLVE_MAX_EMPTIES);
+        let start = std::time::Instant::now();
+        let response = request(line);
+        assert!(start.elapsed() < Duration::from_secs(30), "{:?}", start.elapsed());
+        assert_eq!(response["score"], json!({"type": "exact", "value": 8}));
+        assert_eq!(response["solved"], true);
+    }
+
+    #[test]
+    fn test_limits_caps() {
+        let request: Request = serde_json::from_str(
+            r#"{"cmd":"move","engine":"alphabeta:depth=30","limits":{"exact":60}}"#,
+        )
+        .unwrap();
+        let caps = Limits {
+            depth: 10,
+            exact: 20,
+            playouts: 1000,
+            time: Some(Duration::from_secs(1)),
+        };
+        let limits = request.engine(Some(&caps)).unwrap().limits;
+        assert_eq!((limits.depth, limits.exact, limits.time), (10, 20, caps.time));
+        assert_eq!(request.engine(None).unwrap().limits.time, None);
+        let request: Request =
+            serde_json::from_str(r#"{"cmd":"move","limits":{"playouts":1}}"#).unwrap();
+        assert_eq!(request.engine(Some(&caps)).unwrap().limits.playouts, 2);
+    }
+}
@@ -0,0 +1,68 @@
+// Othello library: bitboards, engines, game records and protocols
+
+use std::fmt;
+
+pub mod bit_lib; // file name
+pub mod alpha_beta; // file name
+pub mod board; // file name
+pub mod book;
+pub mod clock;
+pub mod engine;
+pub mod gtp;
+pub mod jsonl;
+pub mod mcts_dag;
+pub mod monte_carlo;
+pub mod nboard;
+pub mod opening;
+pub mod perft;
+pub mod position;
+pub mod record;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod results;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod server;
+pub mod stats;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tournament;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tui;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+
+pub const BOARD_SIZE: usize = 8;
+pub const BOARDSIZE_SQ: usize = BOARD_SIZE * BOARD_SIZE;
+pub const BLACK_0: u64 = 0x0000_0008_1000_0000;
+pub const WHITE_0: u64 = 0x0000_0010_0800_0000;
+
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub enum TurnPlayer {
+    Black,
+    White,
+}
+impl TurnPlayer {
+    pub fn flip(&self) -> Self {
+        match self {
+            TurnPlayer::Black => TurnPlayer::White,
+            TurnPlayer::White => TurnPlayer::Black,
+        }
+    }
+}
+impl fmt::Display for TurnPlayer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Black => write!(f, "Black"),
+            Self::White => write!(f, "White"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pmove {
+    Pvmove(u64),
+    Passmove,
+    Endnode,
+    Nonenode,
+    Quit,
+    Undo,
+    Redo,
+}
@@ -1,60 +1,35 @@
 // Othello game main
 
-use std::fmt;
+use std::env;
 use std::io;
+use std::sync::Arc;
+use std::thread;
 use std::io::Write;
 use std::time::{Duration, Instant};
 
-mod bit_lib; // file name
-use crate::bit_lib::{can_locate, get_rev_pat}; // bit functions
-use crate::bit_lib::{make_index, print_leagl_moves};
-mod alpha_beta; // file name
-use crate::alpha_beta::alphabeta;
-mod board; // file name
-use crate::board::Board;
-mod monte_carlo;
-use crate::monte_carlo::{mcts, primitive_montecarlo};
-
-const BOARD_SIZE: usize = 8;
-const BOARDSIZE_SQ: usize = BOARD_SIZE * BOARD_SIZE;
-const BLACK_0: u64 = 0x0000_0008_1000_0000;
-const WHITE_0: u64 = 0x0000_0010_0800_0000;
+use othello::bit_lib::{can_locate, get_rev_pat}; // bit functions
+use othello::bit_lib::{make_index, print_leagl_moves};
+use othello::alpha_beta::EvalProfile;
+use othello::board::Board;
+use othello::book::{self, Book, BookMode};
+use othello::clock::{format_clock, TimeControl};
+use othello::engine::{legal_move, Algorithm, Engine};
+use othello::position::Position;
+use othello::record::{save_game, GameRecord};
+use othello::results::ResultWriter;
+use othello::opening;
+use othello::perft;
+use othello::stats::Sprt;
+use othello::tournament::{self, make_entrants, single_opening, Schedule, Tournament};
+use othello::tui::{self, Player};
+use othello::{gtp, jsonl, nboard, server};
+use othello::{Pmove, TurnPlayer};
 
 //#これで黒6石勝ち --> black 10 win
 const BLACK_1: u64 = 0x6000_1008_1012_0500;
 const WHITE_1: u64 = 0x8EFC_EFF7_6F6D_3A3F;
-
-
-#[derive(Clone, Debug, PartialEq, PartialOrd)]
-enum TurnPlayer {
-    Black,
-    White,
-}
-impl TurnPlayer {
-    fn flip(&self) -> Self {
-        match self {
-            TurnPlayer::Black => TurnPlayer::White,
-            TurnPlayer::White => TurnPlayer::Black,
-        }
-    }
-}
-impl fmt::Display for TurnPlayer {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Self::Black => write!(f, "Black"),
-            Self::White => write!(f, "White"),
-        }
-    }
-}
-
-#[derive(Clone, Debug, PartialEq)]
-enum Pmove {
-    Pvmove(u64),
-    Passmove,
-    Endnode,
-    Nonenode,
-    Quit,
-}
+// Mult モードの最大対局数  Upper bound on the games of a Mult match
+const MAX_MATCH_GAMES: usize = 10000;
 
 #[derive(Clone, Debug, PartialEq)]
 enum PlayMode {
@@ -67,15 +42,15 @@ enum PlayMode {
 }
 struct Play {
     play_mode: PlayMode,
+    start: Position,
     board: Board,
-    com_black: fn(u64, u64, usize) -> Pmove,
-    com_white: fn(u64, u64, usize) -> Pmove,
-    win1f: u32,
-    lose1f: u32,
-    draw1f: u32,
+    com_black: Engine,
+    com_white: Engine,
+    time_control: Option<TimeControl>,
+    flagged: Option<TurnPlayer>, // 時間切れ負けの側  The side that lost on time
 }
 impl Play {
-    fn new() -> Self {
+    fn new(start: Option<Position>, time_control: Option<TimeControl>) -> Self {
         let pc1: String = "(BLACK:".to_string();
         let pc2: String = "(White:".to_string();
         let msg = "対戦モードを選択してください:
@@ -89,80 +64,64 @@ impl Play {
             5 => (PlayMode::PcpcMult, pc1, pc2),
             _ => (PlayMode::Test, pc1, pc2),
         };
-        let black_name:&str;
-        let white_name:&str;
-        let meg =
-            "X(Black) のアルゴリズムの選択 (1: alpha_beta, 2: MCTS, 3: primitive_montecarlo) :";
-        let input = Play::select_input(
-            meg, 4, play_mode == PlayMode::Black || play_mode == PlayMode::HumanVsHuman);
-        let com_black = match input {
-            1 => {
-                black_name = "alphabeta)";
-                alphabeta
-            }
-            2 => {
-                black_name = "MCTS)";
-                mcts
-            }
-            3 => {
-                black_name = "primitive_montecarlo)";
-                primitive_montecarlo
-            }
-            _ => {
-                black_name = ")";
-                alphabeta
-            }
-        };
-        let meg =
-            "O(White) のアルゴリズムの選択 (1: alpha_beta, 2: MCTS, 3: primitive_montecarlo) :";
-        let input = Play::select_input(
-            meg, 4, play_mode == PlayMode::White || play_mode == PlayMode::HumanVsHuman);
-        let com_white = match input {
-            1 => {
-                white_name = "alphabeta)";
-                alphabeta
-            }
-            2 => {
-                white_name = "MCTS)";
-                mcts
-            }
-            3 => {
-                white_name = "primitive_montecarlo)";
-                primitive_montecarlo
-            }
-            _ => {
-                white_name = ")";
-                alphabeta
+        let (mut com_black, black_name) = Play::select_engine(
+            "X(Black)",
+            play_mode == PlayMode::Black || play_mode == PlayMode::HumanVsHuman,
+        );
+        let (mut com_white, white_name) = Play::select_engine(
+            "O(White)",
+            play_mode == PlayMode::White || play_mode == PlayMode::HumanVsHuman,
+        );
+        if play_mode != PlayMode::HumanVsHuman {
+            if let Some((book, book_mode)) = Play::select_book() {
+                for com in [&mut com_black, &mut com_white] {
+                    com.book = Some(book.clone());
+                    com.book_mode = book_mode;
+                }
             }
-        };
-        let _com_a = alphabeta;
-        let _com_b = mcts;
-        let _com_c = primitive_montecarlo;
+        }
 
-        let f_player = pc1 + black_name ;
-        let s_player = pc2 + white_name ;
+        let f_player = pc1 + &black_name;
+        let s_player = pc2 + &white_name;
         println!(" set  com_black  {}", f_player);
         println!(" set  com_white  {}", s_player);
-        let mut board = Board::new(
-            (TurnPlayer::Black, f_player.clone(), s_player.clone()),
-            BLACK_0,
-            WHITE_0,
-        );
-        if play_mode == PlayMode::Test {
-            board = Board::new((TurnPlayer::Black, f_player, s_player), BLACK_1, WHITE_1);
+        if let Some(time_control) = &time_control {
+            println!(" set  time       {}", time_control);
         }
+        let start = match start {
+            Some(start) => start,
+            None if play_mode == PlayMode::Test => Position {
+                black: BLACK_1,
+                white: WHITE_1,
+                turn_player: TurnPlayer::Black,
+            },
+            None => Position::default(),
+        };
+        let board = Board::new(
+            (start.turn_player.clone(), f_player, s_player),
+            start.black,
+            start.white,
+        );
         Play {
             play_mode,
+            start,
             board,
             com_black,
             com_white,
-            win1f: 0,
-            lose1f: 0,
-            draw1f: 0,
+            time_control,
+            flagged: None,
         }
     }
 
     fn end_game(&mut self) -> bool {
+        if let Some(loser) = &self.flagged {
+            let winner = match loser {
+                TurnPlayer::Black => format!("後手{}", self.board.second_player),
+                TurnPlayer::White => format!("先手{}", self.board.first_player),
+            };
+            println!("{} の時間切れです {}の勝ちです", loser, winner);
+            return true;
+        }
         if self.board.is_can_play() {
             return false;
         }
@@ -170,18 +129,9 @@ impl Play {
         let white_disk = self.board.white.count_ones();
         let final_score = black_disk as i32 - white_disk as i32;
         let winner = match final_score {
-            x if x > 0 => {
-                self.win1f += 1;
-                format!("先手{}", self.board.first_player)
-            },
-            x if x < 0 => {
-                self.lose1f += 1;
-                format!("後手{}", self.board.second_player)
-            },
-            _ => {
-                self.draw1f += 1;
-                "DRAW".to_string()
-            }
+            x if x > 0 => format!("先手{}", self.board.first_player),
+            x if x < 0 => format!("後手{}", self.board.second_player),
+            _ => "DRAW".to_string(),
         };
         if final_score != 0 {
             println!("{}の {} 石勝ちです", winner, final_score.abs());
@@ -191,7 +141,7 @@ impl Play {
         true
     }
 
-    fn human_input(&self) -> Pmove {
+    fn human_input(&mut self) -> Pmove {
         fn make_pmove(x: usize, y: usize) -> u64 {
             0x8000000000000000 >> (x + y * 8)
         }
@@ -200,7 +150,9 @@ impl Play {
             TurnPlayer::White => "O:WHITE",
         };
         println!(
-            "あなたの番です({}), 次の手を入力してください (q:quit p:pass)",
+            "あなたの番です({}), 次の手を入力してください \
+             (q:quit p:pass u:undo r:redo hint eval analyze save <file> load <file> [n] \
+             setup <position>)",
             player_str
         );
         print_leagl_moves(self.board.first, self.board.second);
@@ -215,6 +167,32 @@ impl Play {
                     println!("ゲームを終了します");
                     return Pmove::Quit;
                 }
+                "u" | "undo" => return Pmove::Undo,
+                _ if buffer.starts_with("save ") => {
+                    if let Err(e) = save_game(&self.board, buffer[5..].trim()) {
+                        println!("保存できません: {}", e);
+                    }
+                }
+                _ if buffer.starts_with("setup ") => {
+                    if self.setup(&buffer[6..]) {
+                        return Pmove::Nonenode;
+                    }
+                }
+                _ if buffer.starts_with("load ") => {
+                    let mut args = buffer[5..].split_whitespace();
+                    let path = args.next().unwrap_or("");
+                    let upto = args.next().and_then(|n| n.parse::<usize>().ok());
+                    match GameRecord::load(path) {
+                        Ok(record) => match record.restore(&mut self.board, upto) {
+                            // 盤面が変わったので再表示する  The board changed, show it again
+                            Ok(()) => return Pmove::Nonenode,
+                            Err(e) => println!("棋譜を再生できません: {}", e),
+                        },
+                        Err(e) => println!("読み込めません: {}", e),
+                    }
+                }
+                "r" | "redo" => return Pmove::Redo,
+                "hint" | "eval" | "analyze" => self.advise(buffer),
                 "p" | "pass" => {
                     if can_locate(self.board.first, self.board.second) == 0 {
                         println!("パスです");
@@ -242,87 +220,346 @@ impl Play {
         }
     }
 
-    fn com_think(&mut self) -> Pmove {
-        if self.play_mode != PlayMode::PcpcMult {
-            println!("{} コンピュータ思考中...", self.board.turn_player);
+    // 対局相手のエンジンで今の局面を調べる  Examine the position with the opponent's engine
+    fn advise(&self, command: &str) {
+        let engine = match (&self.play_mode, &self.board.turn_player) {
+            (PlayMode::Black, _) => &self.com_white,
+            (PlayMode::White, _) => &self.com_black,
+            (_, TurnPlayer::Black) => &self.com_black,
+            (_, TurnPlayer::White) => &self.com_white,
         };
-        let pm = if self.board.turn_player == TurnPlayer::Black {
-            (self.com_black)(self.board.first, self.board.second, self.board.left)
+        let (first, second, left) = (self.board.first, self.board.second, self.board.left);
+        if can_locate(first, second) == 0 {
+            println!("打てる手がありません");
+            return;
+        }
+        if command == "hint" {
+            if let Some(mv) = engine.book_move(first, second) {
+                println!("ヒント: {} (定石)", make_index(mv));
+                return;
+            }
+        }
+        println!("{} 考慮中...", engine);
+        let analysis = engine.analyze(first, second, left);
+        let (best, score) = &analysis.moves[0];
+        match command {
+            "hint" => println!("ヒント: {} (評価 {})", make_index(*best), score),
+            "eval" => {
+                print!(
+                    "評価: {} (手番側から見た値)  読み筋: {}  深さ: {}  ノード: {}",
+                    score,
+                    analysis.pv_text(),
+                    analysis.depth,
+                    analysis.nodes
+                );
+                if let Some(bytes) = analysis.memory_per_simulation() {
+                    let memory = analysis.memory;
+                    print!("  メモリ: {} バイト ({:.0} バイト/シミュレーション)", memory, bytes);
+                }
+                println!();
+            }
+            _ => {
+                for (rank, (mv, score)) in analysis.moves.iter().enumerate() {
+                    println!("{:3}. {} {:>8}", rank + 1, make_index(*mv), score.to_string());
+                }
+            }
+        }
+    }
+
+    // 指定した局面から始め直す  Restart from the given position
+    fn setup(&mut self, text: &str) -> bool {
+        match Position::parse(text) {
+            Ok(position) => {
+                println!("局面: {}", position);
+                println!("      {}", position.to_hex());
+                self.board.reset(position.black, position.white, position.turn_player);
+                true
+            }
+            Err(e) => {
+                println!("局面を読み込めません: {}", e);
+                false
+            }
+        }
+    }
+
+    // 終了した対局を保存する  Offer to save the finished or quit game
+    fn offer_save(&self) {
+        println!("棋譜を保存するファイル名を入力してください (Enter: 保存しない)");
+        let mut buffer = String::new();
+        io::stdin()
+            .read_line(&mut buffer)
+            .expect("Failed to read line");
+        let path = buffer.trim();
+        if !path.is_empty() {
+            if let Err(e) = save_game(&self.board, path) {
+                println!("保存できません: {}", e);
+            }
+        }
+    }
+
+    fn is_human_turn(&self) -> bool {
+        (self.board.turn_player == TurnPlayer::Black && self.play_mode == PlayMode::Black)
+            || (self.board.turn_player == TurnPlayer::White && self.play_mode == PlayMode::White)
+            || self.play_mode == PlayMode::HumanVsHuman
+    }
+
+    // 人間の一つ前の手番まで戻す パスの局面は飛ばす
+    // Step back to the human's previous turn, skipping positions where a pass was played
+    fn undo_move(&mut self) {
+        let mut undone = 0;
+        while let Some(pmove) = self.board.undo() {
+            undone += 1;
+            if self.is_human_turn() && pmove != Pmove::Passmove {
+                println!("{} 手戻しました", undone);
+                return;
+            }
+        }
+        for _ in 0..undone {
+            self.board.redo();
+        }
+        println!("これ以上戻せません");
+    }
+
+    // 戻した手を人間の次の手番まで打ち直す  Redo up to the human's next turn
+    fn redo_move(&mut self) {
+        if self.board.redo().is_none() {
+            println!("やり直す手がありません");
+            return;
+        }
+        while self.board.next_redo().is_some()
+            && (!self.is_human_turn() || self.board.next_redo() == Some(&Pmove::Passmove))
+        {
+            self.board.redo();
+        }
+    }
+
+    fn com_think(&mut self) -> Pmove {
+        println!("{} コンピュータ思考中...", self.board.turn_player);
+        let engine = if self.board.turn_player == TurnPlayer::Black {
+            &self.com_black
         } else {
-            (self.com_white)(self.board.first, self.board.second, self.board.left)
+            &self.com_white
         };
-        if self.play_mode != PlayMode::PcpcMult {
-            match pm {
-                Pmove::Pvmove(mv) => println!("PC の着手： {}", make_index(mv)),
-                Pmove::Passmove => println!("パスします"),
-                _ => {}
+        let (first, second, left) = (self.board.first, self.board.second, self.board.left);
+        // 時計があれば残り時間から考慮時間を決める  Allot the thinking time from the clock
+        let pm = match &self.time_control {
+            Some(time_control) => {
+                engine.think_for(first, second, left, time_control.budget(&self.board))
             }
+            None => engine.think(first, second, left),
+        };
+        let pm = legal_move(pm, first, second);
+        match pm {
+            Pmove::Pvmove(mv) => println!("PC の着手： {}", make_index(mv)),
+            Pmove::Passmove => println!("パスします"),
+            _ => {}
         }
         pm
     }
 
     fn loop_game(&mut self) {
-        if self.play_mode != PlayMode::PcpcMult {
-            loop {
-                self.board.print_board();
-                if self.end_game() {
-                    break;
-                }
-                if self.play_mode == PlayMode::Test {
-                    print!("q is quit: ");
-                    io::stdout().flush().unwrap();
-                    let mut buffer = String::new();
-                    io::stdin()
-                        .read_line(&mut buffer)
-                        .expect("Failed to read line");
-                    if buffer.trim() == "q" {
-                        return;
-                    }
-                }
-                let pmove = if (self.board.turn_player == TurnPlayer::Black
-                    && self.play_mode == PlayMode::Black)
-                    || (self.board.turn_player == TurnPlayer::White
-                        && self.play_mode == PlayMode::White)
-                    || self.play_mode == PlayMode::HumanVsHuman
-                {
-                    self.human_input()
-                } else {
-                    self.com_think()
-                };
-                if let Pmove::Quit = pmove {
+        if self.play_mode == PlayMode::PcpcMult {
+            self.run_match();
+            return;
+        }
+        loop {
+            self.board.print_board();
+            self.print_clocks();
+            if self.end_game() {
+                break;
+            }
+            if self.play_mode == PlayMode::Test {
+                print!("q is quit, setup <position>: ");
+                io::stdout().flush().unwrap();
+                let mut buffer = String::new();
+                io::stdin()
+                    .read_line(&mut buffer)
+                    .expect("Failed to read line");
+                if buffer.trim() == "q" {
                     return;
                 }
-                self.board.update_board(pmove);
+                if let Some(text) = buffer.trim().strip_prefix("setup ") {
+                    self.setup(text);
+                    continue;
+                }
             }
-        } else {
-            for game_count in 0..50 {
-                let start = Instant::now();
+            let start = Instant::now();
+            let pmove = if self.is_human_turn() {
+                self.human_input()
+            } else {
+                self.com_think()
+            };
+            match pmove {
+                Pmove::Quit => break,
+                Pmove::Undo => self.undo_move(),
+                Pmove::Redo => self.redo_move(),
+                Pmove::Nonenode => {}
+                _ => self.play_move(pmove, start.elapsed()),
+            }
+        }
+        self.offer_save();
+    }
 
-                self.board = Board::new(
-                    (
-                        TurnPlayer::Black,
-                        self.board.first_player.clone(),
-                        self.board.second_player.clone(),
-                    ),
-                    BLACK_0,
-                    WHITE_0,
-                );
-                loop {
-                    if self.end_game() {
-                        break;
-                    }
-                    let pmove = self.com_think();
-                    self.board.update_board(pmove);
+    // 選んだ2つのエンジンで先後を入れ替えながら対戦する
+    // Play the two selected engines against each other, swapping colours every game
+    fn run_match(&self) {
+        let msg = format!("対戦数を入力してください (1から{}):", MAX_MATCH_GAMES);
+        let games = Play::select_input(&msg, MAX_MATCH_GAMES, false);
+        let cores = thread::available_parallelism().map_or(1, |n| n.get());
+        let msg = format!("同時に進める対局数を入力してください (1から{}):", cores);
+        let threads = Play::select_input(&msg, cores, false);
+        let sprt = Play::select_sprt();
+        let mut writer = Play::select_writer();
+        let tournament = Tournament {
+            entrants: make_entrants(vec![
+                (self.com_black.to_string(), self.com_black.clone()),
+                (self.com_white.to_string(), self.com_white.clone()),
+            ]),
+            schedule: Schedule::RoundRobin,
+            games,
+            alternate: true,
+            time_control: self.time_control.clone(),
+            openings: single_opening(self.start.clone()),
+            sprt,
+            threads,
+            seed: rand::random(),
+        };
+        let mut error = Ok(());
+        let crosstable = tournament.run(|number, result, crosstable| {
+            tournament::print_game(number, games, &tournament, result, crosstable);
+            if error.is_ok() {
+                error = writer.write(number, &tournament, result);
+            }
+        });
+        print!("\n{}", crosstable);
+        if let Err(e) = error {
+            println!("結果を書き出せません: {}", e);
+        }
+    }
+
+    // 時計を確かめてから着手する  Check the clock, then play the move
+    fn play_move(&mut self, pmove: Pmove, used: Duration) {
+        if let Some(time_control) = &self.time_control {
+            if time_control.is_flagged(&self.board, used) {
+                self.flagged = Some(self.board.turn_player.clone());
+                return;
+            }
+        }
+        self.board.update_board_timed(pmove, used);
+    }
+
+    fn print_clocks(&self) {
+        let Some(time_control) = &self.time_control else {
+            return;
+        };
+        if let TimeControl::PerMove(time) = time_control {
+            println!("持ち時間: 1手 {:.1} 秒", time.as_secs_f64());
+            return;
+        }
+        println!(
+            "残り時間 X: {}  O: {}",
+            format_clock(time_control.remaining(&self.board, &TurnPlayer::Black)),
+            format_clock(time_control.remaining(&self.board, &TurnPlayer::White))
+        );
+    }
+
+    fn select_engine(color: &str, human: bool) -> (Engine, String) {
+        let msg = format!(
+            "{} のアルゴリズムの選択 (1: alpha_beta, 2: MCTS, 3: primitive_montecarlo, 4: MCTS_DAG) :",
+            color
+        );
+        let input = Play::select_input(&msg, 4, human);
+        let mut engine = match input {
+            1 => Engine::new(Algorithm::AlphaBeta),
+            2 => Engine::new(Algorithm::Mcts),
+            3 => Engine::new(Algorithm::PrimitiveMontecarlo),
+            4 => Engine::new(Algorithm::MctsDag),
+            _ => return (Engine::new(Algorithm::AlphaBeta), ")".to_string()),
+        };
+        if engine.algorithm == Algorithm::AlphaBeta {
+            engine.eval = Play::select_profile();
+        }
+        let name = format!("{})", engine);
+        (engine, name)
+    }
+    fn select_profile() -> EvalProfile {
+        loop {
+            println!(
+                "評価プロファイルを入力してください (Enter: default {})",
+                EvalProfile::default()
+            );
+            println!("  例: table=1,stones=2,square=3,mobility=off (table: 1,2,3,4,6)");
+            let mut buffer = String::new();
+            io::stdin()
+                .read_line(&mut buffer)
+                .expect("Failed to read line");
+            match EvalProfile::parse(buffer.trim()) {
+                Ok(profile) => return profile,
+                Err(e) => println!("無効な入力です: {}", e),
+            }
+        }
+    }
+    fn select_book() -> Option<(Arc<Book>, BookMode)> {
+        loop {
+            println!("定石ファイルを入力してください (Enter: 定石を使わない)");
+            let mut buffer = String::new();
+            io::stdin()
+                .read_line(&mut buffer)
+                .expect("Failed to read line");
+            let path = buffer.trim();
+            if path.is_empty() {
+                return None;
+            }
+            match Book::load(path) {
+                Ok(book) => {
+                    println!("定石 {} 局面", book.len());
+                    let msg = "定石の使い方 (1: best, 2: weighted random, 3: off) :";
+                    let book_mode = match Play::select_input(msg, 3, false) {
+                        1 => BookMode::Best,
+                        2 => BookMode::Random,
+                        _ => BookMode::Off,
+                    };
+                    return Some((Arc::new(book), book_mode));
                 }
-                println!(
-                    " 先手： {} 勝  後手： {} 勝  引き分け： {}  試合数： {}",
-                    self.win1f,
-                    self.lose1f,
-                    self.draw1f,
-                    game_count + 1
-                );
-                let duration: Duration = start.elapsed();
-                println!("Time elapsed in alphabet is: {:?}", duration);
+                Err(e) => println!("定石を読み込めません: {}", e),
+            }
+        }
+    }
+    // 2つのエンジンの対戦を SPRT で打ち切る  An SPRT that stops the match early
+    fn select_sprt() -> Option<Sprt> {
+        loop {
+            println!("SPRT を入力してください 例: 0,20 (elo0,elo1[,alpha,beta]) (Enter: 打ち切らない)");
+            let mut buffer = String::new();
+            io::stdin()
+                .read_line(&mut buffer)
+                .expect("Failed to read line");
+            if buffer.trim().is_empty() {
+                return None;
+            }
+            match Sprt::parse(buffer.trim()) {
+                Ok(sprt) => return Some(sprt),
+                Err(e) => println!("無効な入力です: {}", e),
+            }
+        }
+    }
+    // 1局ごとの結果を書き出す CSV, JSON のファイルと GGF のディレクトリ
+    // CSV and JSON files and a GGF directory for the per-game results
+    fn select_writer() -> ResultWriter {
+        let read_path = |msg: &str| {
+            println!("{} (Enter: 書き出さない)", msg);
+            let mut buffer = String::new();
+            io::stdin()
+                .read_line(&mut buffer)
+                .expect("Failed to read line");
+            Some(buffer.trim().to_string()).filter(|path| !path.is_empty())
+        };
+        loop {
+            let csv = read_path("結果を書き出す CSV ファイルを入力してください");
+            let json = read_path("結果を書き出す JSON ファイルを入力してください");
+            let ggf = read_path("棋譜を書き出す GGF のディレクトリを入力してください");
+            match ResultWriter::new(csv.as_deref(), json.as_deref(), ggf.as_deref()) {
+                Ok(writer) => return writer,
+                Err(e) => println!("書き出せません: {}", e),
             }
         }
     }
@@ -354,8 +591,100 @@ impl Play {
     }
 }
 
+// "--name value" を取り除いて value を返す 値がなければエラー
+// Remove "--name value" from args, an error when the value is missing
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    let Some(index) = args.iter().position(|arg| arg == name) else {
+        return Ok(None);
+    };
+    args.remove(index);
+    match args.get(index) {
+        Some(value) if !value.starts_with("--") => Ok(Some(args.remove(index))),
+        _ => Err(format!("{} needs a value", name)),
+    }
+}
+
+fn exit_on_error(result: Result<(), String>) {
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
 fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let options = take_option(&mut args, "--time").and_then(|time| {
+        let time = time.map(|text| TimeControl::parse(&text).map_err(|e| format!("--time: {}", e)));
+        let start = take_option(&mut args, "--position")?
+            .map(|text| Position::parse(&text).map_err(|e| format!("--position: {}", e)));
+        Ok((time.transpose()?, start.transpose()?))
+    });
+    let (time_control, start) = options.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    // 時計を使うのは対局, match と tui だけ  Only games, match and tui keep a clock
+    if let (Some(_), Some(mode)) = (&time_control, args.first()) {
+        let untimed = ["book", "json", "openings", "perft", "serve", "gtp", "nboard"];
+        if untimed.contains(&mode.as_str()) {
+            return exit_on_error(Err(format!("--time is not supported by {}", mode)));
+        }
+    }
+    match args.first().map(|s| s.as_str()) {
+        Some("book") => return exit_on_error(book::run_command(&args[1..])),
+        Some("json") => return jsonl::run(),
+        Some("openings") => return exit_on_error(opening::run_command(&args[1..])),
+        Some("perft") => {
+            return exit_on_error(perft::run_command(&args[1..], start.unwrap_or_default()));
+        }
+        Some("match") => {
+            let start = start.unwrap_or_default();
+            return exit_on_error(tournament::run_command(&args[1..], start, time_control));
+        }
+        Some("tui") => {
+            let player = |index: usize, default: &str| {
+                Player::parse(args.get(index).map(|s| s.as_str()).unwrap_or(default))
+            };
+            let players = player(1, "human").and_then(|black| Ok((black, player(2, "alphabeta")?)));
+            return exit_on_error(players.and_then(|(black, white)| {
+                tui::run(black, white, start.unwrap_or_default(), time_control)
+            }));
+        }
+        Some("serve") => {
+            let port = match args.get(1).map(|port| port.parse::<u16>()) {
+                Some(Ok(port)) => port,
+                Some(Err(_)) => return exit_on_error(Err(format!("bad port: {}", args[1]))),
+                None => server::DEFAULT_PORT,
+            };
+            return exit_on_error(server::run(port));
+        }
+        Some(mode @ ("gtp" | "nboard")) => {
+            let spec = args.get(1).map(|s| s.as_str()).unwrap_or("alphabeta");
+            let engine = Engine::parse(spec).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            let start = start.unwrap_or_default();
+            return match mode {
+                "gtp" => gtp::run(engine, start),
+                _ => nboard::run(engine, start),
+            };
+        }
+        Some(arg) => {
+            exit_on_error(Err(format!(
+                "unknown argument: {}\nusage: Othello [--position <position>] [--time <time control>] | Othello book ... \
+                 | Othello gtp [engine] | Othello nboard [engine] | Othello json \
+                 | Othello serve [port] | Othello tui [black] [white] \
+                 | Othello match <engine> <engine>... [--games N] [--gauntlet] [--no-alternate] \
+                 [--openings <file|plies>] [--sprt elo0,elo1[,alpha,beta]] [--threads N] [--seed N] \
+                 [--csv <file>] [--json <file>] [--ggf <dir>] \
+                 | Othello openings <plies> <out> [--depth D] [--margin M] | Othello perft <depth> [--divide] [--compare] | Othello perft bench [rounds]",
+                arg
+            )));
+        }
+        None => {}
+    }
     println!("Othello Reversi program ");
-    let mut play = Play::new();
+    let mut play = Play::new(start, time_control);
     play.loop_game();
 }
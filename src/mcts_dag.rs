@@ -0,0 +1,333 @@
+// MCTS on a DAG: transpositions share one node and its statistics
+// 局面のハッシュで引く配列に節点を置くので、違う手順で同じ局面に来ても統計は1つ
+// Nodes live in an arena looked up by position hash, so every move order reaching a position
+// adds to the same statistics
+
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::time::Instant;
+
+use rand::Rng;
+
+// from  bit_lib
+use crate::bit_lib::{can_locate, get_rev_pat, zobrist};
+use crate::engine::Limits;
+use crate::monte_carlo::with_random;
+use crate::{Pmove, TurnPlayer}; // from lib
+
+struct DagNode {
+    my_piece: u64, // 手番側  side to move
+    en_piece: u64,
+    visits: u32,
+    // この局面に打った側の勝ち2 引き分け1  2 per win and 1 per draw for the side that moved here
+    wins: u32,
+    // (着手, 節点の番号) パスは 0  (move, node index), 0 is a pass
+    children: Vec<(u64, usize)>,
+    expanded: bool,
+    // 終局なら手番側から見た勝ち負け  The result for the side to move once the game is over
+    result: Option<i32>,
+}
+
+// 節点の配列とハッシュからの索引  The node arena and its index by hash
+pub struct Dag {
+    nodes: Vec<DagNode>,
+    index: HashMap<u64, usize>,
+    // 既にある節点に合流した回数  Times an edge joined an existing node
+    pub transpositions: u64,
+}
+
+impl Default for Dag {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Dag {
+    pub fn new() -> Self {
+        Dag {
+            nodes: Vec::new(),
+            index: HashMap::new(),
+            transpositions: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // 局面の節点 なければ作る  The node of a position, created when it is new
+    fn node(&mut self, my_piece: u64, en_piece: u64) -> usize {
+        // 手番側を黒として引く (色によらない)  Keyed with the side to move as Black
+        let key = zobrist(my_piece, en_piece, &TurnPlayer::Black);
+        if let Some(&index) = self.index.get(&key) {
+            let node = &self.nodes[index];
+            if node.my_piece == my_piece && node.en_piece == en_piece {
+                self.transpositions += 1;
+                return index;
+            }
+            // ハッシュの衝突 共有しない節点にする  A hash collision gets an unshared node
+        } else {
+            self.index.insert(key, self.nodes.len());
+        }
+        self.nodes.push(DagNode {
+            my_piece,
+            en_piece,
+            visits: 0,
+            wins: 0,
+            children: Vec::new(),
+            expanded: false,
+            result: None,
+        });
+        self.nodes.len() - 1
+    }
+
+    fn expand(&mut self, index: usize) {
+        let (my, en) = (self.nodes[index].my_piece, self.nodes[index].en_piece);
+        let mut moves = can_locate(my, en);
+        let mut children = Vec::with_capacity(moves.count_ones() as usize);
+        if moves == 0 {
+            if can_locate(en, my) == 0 {
+                self.nodes[index].result = Some(final_result(my, en));
+            } else {
+                children.push((0, self.node(en, my)));
+            }
+        }
+        while moves != 0 {
+            let mv = moves & moves.wrapping_neg();
+            moves ^= mv;
+            let rev = get_rev_pat(my, en, mv);
+            children.push((mv, self.node(en ^ rev, my ^ (mv | rev))));
+        }
+        let node = &mut self.nodes[index];
+        node.children = children;
+        node.expanded = true;
+    }
+
+    // 1回のシミュレーション 手番側から見た勝ち1 引き分け0 負け-1
+    // One simulation, 1 for a win of the side to move, 0 for a draw and -1 for a loss
+    fn simulate(&mut self, index: usize) -> i32 {
+        let node = &self.nodes[index];
+        let result = if let Some(result) = node.result {
+            result
+        } else if node.visits == 0 {
+            rollout(node.my_piece, node.en_piece)
+        } else {
+            if !node.expanded {
+                self.expand(index);
+            }
+            match self.select(index) {
+                Some(child) => -self.simulate(child),
+                None => self.nodes[index].result.unwrap_or(0),
+            }
+        };
+        let node = &mut self.nodes[index];
+        node.visits += 1;
+        node.wins += (1 - result) as u32;
+        result
+    }
+
+    // UCB1 で子を選ぶ 子の統計は他の親からの訪問も含む
+    // Pick a child by UCB1, the child statistics include visits through other parents
+    fn select(&self, index: usize) -> Option<usize> {
+        let parent = &self.nodes[index];
+        let log_n = (parent.visits.max(1) as f32).log10();
+        let mut best = None;
+        let mut max_ucb = f32::MIN;
+        for &(_, child) in &parent.children {
+            let node = &self.nodes[child];
+            if node.visits == 0 {
+                return Some(child);
+            }
+            let ucb = node.wins as f32 / node.visits as f32
+                + 2.0 * (log_n / node.visits as f32).sqrt();
+            if max_ucb < ucb {
+                max_ucb = ucb;
+                best = Some(child);
+            }
+        }
+        best
+    }
+
+    // 配列, 子の並び, 索引のおよそのバイト数  Approximate bytes of the arena, edges and index
+    pub fn memory(&self) -> usize {
+        let edges: usize = self.nodes.iter().map(|node| node.children.capacity()).sum();
+        self.nodes.capacity() * size_of::<DagNode>()
+            + edges * size_of::<(u64, usize)>()
+            + self.index.capacity() * (size_of::<(u64, usize)>() + 1)
+    }
+}
+
+fn final_result(my_piece: u64, en_piece: u64) -> i32 {
+    (my_piece.count_ones() as i32 - en_piece.count_ones() as i32).signum()
+}
+
+// 終局までランダムに打つ 手番側から見た勝ち負け  Random moves to the end, the result for the
+// side to move
+fn rollout(my_piece: u64, en_piece: u64) -> i32 {
+    let (mut my, mut en) = (my_piece, en_piece);
+    let mut sign = 1;
+    loop {
+        let mut moves = can_locate(my, en);
+        if moves == 0 {
+            if can_locate(en, my) == 0 {
+                return sign * final_result(my, en);
+            }
+            (my, en) = (en, my);
+            sign = -sign;
+            continue;
+        }
+        let pick = with_random(|rng| rng.gen_range(0..moves.count_ones()));
+        for _ in 0..pick {
+            moves &= moves - 1;
+        }
+        let mv = moves & moves.wrapping_neg();
+        let rev = get_rev_pat(my, en, mv);
+        (my, en) = (en ^ rev, my ^ (mv | rev));
+        sign = -sign;
+    }
+}
+
+// limits.playouts 回か時間まで探索した DAG と根の節点  Search until limits.playouts
+// simulations or the time limit, returning the DAG and the root node
+fn search(black: u64, white: u64, limits: &Limits) -> (Dag, usize) {
+    let mut dag = Dag::new();
+    let root = dag.node(black, white);
+    let deadline = limits.time.map(|time| Instant::now() + time);
+    for _ in 0..limits.playouts {
+        dag.simulate(root);
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            break;
+        }
+    }
+    (dag, root)
+}
+
+pub fn mcts_dag(black: u64, white: u64, _left: usize, limits: &Limits) -> Pmove {
+    let moves = can_locate(black, white);
+    if moves == 0 {
+        return Pmove::Passmove;
+    }
+    if moves.count_ones() == 1 {
+        return Pmove::Pvmove(moves);
+    }
+    let (dag, root) = search(black, white, limits);
+    // 時間切れで根が展開されていなければ最初の合法手  The first legal move if the deadline
+    // passed before the root was expanded
+    let first = moves & moves.wrapping_neg();
+    dag.nodes[root]
+        .children
+        .iter()
+        .max_by_key(|&&(_, child)| dag.nodes[child].visits)
+        .map_or(Pmove::Pvmove(first), |&(mv, _)| Pmove::Pvmove(mv))
+}
+
+// 各手の勝率 (訪問回数の多い順), シミュレーション回数, 最も多く訪れた手順, DAG のバイト数
+// Win rate of each move (most visited first), the number of simulations, the most visited
+// line (0 is a pass) and the bytes used by the DAG
+pub fn mcts_dag_scores(
+    black: u64,
+    white: u64,
+    _left: usize,
+    limits: &Limits,
+) -> (Vec<(u64, f64)>, u64, Vec<u64>, usize) {
+    let moves = can_locate(black, white);
+    if moves == 0 {
+        // パスしかなければ score_moves と同じく空  A pass-only root is empty, as in score_moves
+        return (Vec::new(), 0, Vec::new(), 0);
+    }
+    if moves.count_ones() == 1 {
+        // 1手しかなければ探索しない  A single move is not searched
+        return (vec![(moves, 0.5)], 0, vec![moves], 0);
+    }
+    let (dag, root) = search(black, white, limits);
+    let mut children = dag.nodes[root].children.clone();
+    children.sort_by_key(|&(_, child)| std::cmp::Reverse(dag.nodes[child].visits));
+    let scores = children
+        .iter()
+        .map(|&(mv, child)| {
+            let node = &dag.nodes[child];
+            let rate = match node.visits {
+                0 => 0.5,
+                n => node.wins as f64 / (2 * n) as f64,
+            };
+            (mv, rate)
+        })
+        .collect();
+    let mut pv = Vec::new();
+    let mut current = root;
+    while let Some(&(mv, child)) =
+        dag.nodes[current].children.iter().max_by_key(|&&(_, child)| dag.nodes[child].visits)
+    {
+        if dag.nodes[child].visits == 0 {
+            break;
+        }
+        pv.push(mv);
+        current = child;
+    }
+    (scores, dag.nodes[root].visits as u64, pv, dag.memory())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Algorithm, Engine};
+    use crate::monte_carlo::seed_random;
+    use crate::position::Position;
+    use crate::{BLACK_0, WHITE_0};
+
+    #[test]
+    fn test_transpositions() {
+        // f5f6e6 と e6f6f5 は同じ局面  f5f6e6 and e6f6f5 reach the same position
+        let a = Position::parse("f5f6e6").unwrap();
+        let b = Position::parse("e6f6f5").unwrap();
+        assert_eq!(a, b);
+        let mut dag = Dag::new();
+        let first = dag.node(a.white, a.black);
+        assert_eq!(dag.node(b.white, b.black), first);
+        assert_eq!((dag.len(), dag.transpositions), (1, 1));
+
+        seed_random(1);
+        let limits = Limits { playouts: 3000, ..Limits::new(&Algorithm::MctsDag) };
+        let (dag, root) = search(BLACK_0, WHITE_0, &limits);
+        assert_eq!(dag.nodes[root].visits, 3000);
+        assert!(dag.transpositions > 0);
+        assert!(dag.memory() > 0);
+    }
+
+    #[test]
+    fn test_mcts_dag() {
+        let engine = Engine::parse("mcts_dag:playouts=500").unwrap();
+        let analysis = engine.analyze(BLACK_0, WHITE_0, 60);
+        assert_eq!(analysis.moves.len(), 4);
+        assert_eq!(analysis.nodes, 500);
+        assert!(analysis.memory_per_simulation().is_some());
+        // 読み切りと同じ最善手  The same best move as a complete alphabeta read
+        let black: u64 = 0x6000_1008_1012_0500;
+        let white: u64 = 0x8EFC_EFF7_6F6D_3A3F;
+        let left = 64 - (black | white).count_ones() as usize;
+        let exact = Engine::new(Algorithm::AlphaBeta).analyze(black, white, left);
+        seed_random(2);
+        let engine = Engine::parse("mcts_dag:playouts=3000").unwrap();
+        let analysis = engine.analyze(black, white, left);
+        assert_eq!(analysis.moves[0].0, exact.moves[0].0);
+        let pmove = engine.think(black, white, left);
+        assert_eq!(pmove, Pmove::Pvmove(exact.moves[0].0));
+        // 根が展開される前に止まっても合法手  A legal move when the root is never expanded
+        let engine = Engine::parse("mcts_dag:playouts=1").unwrap();
+        let pmove = engine.think(BLACK_0, WHITE_0, 60);
+        assert!(matches!(pmove, Pmove::Pvmove(mv) if mv & can_locate(BLACK_0, WHITE_0) != 0));
+    }
+
+    #[test]
+    fn test_pass_root() {
+        // B1 の手番は打てずにパス  B1 has no move and must pass
+        let (b1, a1) = (1 << 62, 1 << 63);
+        let limits = Limits { playouts: 100, ..Limits::new(&Algorithm::MctsDag) };
+        assert_eq!(mcts_dag_scores(b1, a1, 62, &limits), (Vec::new(), 0, Vec::new(), 0));
+        assert_eq!(mcts_dag(b1, a1, 62, &limits), Pmove::Passmove);
+    }
+}
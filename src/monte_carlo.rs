@@ -1,15 +1,35 @@
 // monte carlo library AI for Othello
 
+use std::cell::RefCell;
 use std::fmt;
+use std::mem::size_of;
+use std::time::Instant;
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::SeedableRng;
 
 // from  bit_lib
 use crate::bit_lib::{can_locate, get_rev_pat, make_index}; // bit functions
+use crate::engine::Limits;
 use crate::{Pmove, TurnPlayer}; // from main
 
 const THRESHOLD: u32 = 1;
 
+thread_local! {
+    // スレッドごとの乱数  Per-thread random numbers for rollouts and the book
+    static RANDOM: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+// このスレッドの乱数の種を決める 時間制限のないエンジンの対局が再現できる
+// Seed this thread's random numbers, so games of engines without a time limit repeat
+pub fn seed_random(seed: u64) {
+    RANDOM.with(|random| *random.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+pub fn with_random<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    RANDOM.with(|random| f(&mut random.borrow_mut()))
+}
+
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 enum WinLose {
     Win,
@@ -106,6 +126,13 @@ impl Node {
             self.step,
         )
     }
+    // この節点から下の木のおよそのバイト数  Approximate bytes of the tree below this node
+    fn memory(&self) -> usize {
+        size_of::<Node>()
+            + self.legal_actions.capacity() * size_of::<u64>()
+            + (self.child_nodes.capacity() - self.child_nodes.len()) * size_of::<Node>()
+            + self.child_nodes.iter().map(Node::memory).sum::<usize>()
+    }
     fn ucb1_update(&mut self, all_n: u32) {
         self.ucb1 = self.w_sa as f32 / self.n_sa as f32
             + 2.0 * ((all_n as f32).log10() / self.n_sa as f32).sqrt();
@@ -125,7 +152,7 @@ fn make_legal_actions(my_piece: u64, en_piece: u64) -> Vec<u64> {
 }
 
 // MCTS
-pub fn mcts_s(node: &mut Node) -> Pmove {
+pub fn mcts_s(node: &mut Node, limits: &Limits) -> Pmove {
     fn evaluate(node: &mut Node) -> i32 {
         node.n_sa += 1;
         if node.n_sa == 1 {
@@ -260,15 +287,21 @@ pub fn mcts_s(node: &mut Node) -> Pmove {
         // only one move
         return Pmove::Pvmove(*node.legal_actions.first().unwrap());
     };
-    // for evaluatte 5000 times
-    for _ in 0..5000 {
+    // for evaluatte limits.playouts (5000) times or until the time limit
+    let deadline = limits.time.map(|time| Instant::now() + time);
+    for _ in 0..limits.playouts {
         evaluate(node);
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            break;
+        }
     }
     //println!("{}", node);
     //println!("---------");
     // choose best move
+    // 時間切れで根が展開されていなければ最初の合法手  The first legal move if the deadline
+    // passed before the root was expanded
     let mut n_sa_max: u32 = 0;
-    let mut max_action: u64 = 0;
+    let mut max_action: u64 = node.legal_actions[0];
     for ch_node in node.child_nodes.iter() {
         if n_sa_max < ch_node.n_sa {
             n_sa_max = ch_node.n_sa;
@@ -278,29 +311,89 @@ pub fn mcts_s(node: &mut Node) -> Pmove {
     Pmove::Pvmove(max_action)
 }
 
-pub fn mcts(black: u64, white: u64, _left: usize) -> Pmove {
+pub fn mcts(black: u64, white: u64, _left: usize, limits: &Limits) -> Pmove {
     let mut node = Node::new(TurnPlayer::Black, black, white, 0, 0);
-    mcts_s(&mut node)
+    mcts_s(&mut node, limits)
+}
+
+// 各手の勝率 (引き分けは半分, 訪問回数の多い順), シミュレーション回数, 最も多く訪れた手順と
+// 木のバイト数  Win rate of each move (a draw counts half, most visited first), the number of
+// simulations, the most visited line (0 is a pass) and the bytes used by the tree
+pub fn mcts_scores(
+    black: u64,
+    white: u64,
+    _left: usize,
+    limits: &Limits,
+) -> (Vec<(u64, f64)>, u64, Vec<u64>, usize) {
+    let mut node = Node::new(TurnPlayer::Black, black, white, 0, 0);
+    if node.legal_actions.is_empty() {
+        // パスしかなければ score_moves と同じく空  A pass-only root is empty, as in score_moves
+        return (Vec::new(), 0, Vec::new(), 0);
+    }
+    if node.legal_actions.len() == 1 {
+        // 1手しかなければ探索しない  A single move is not searched
+        let mv = node.legal_actions[0];
+        return (vec![(mv, 0.5)], 0, vec![mv], 0);
+    }
+    mcts_s(&mut node, limits);
+    let mut children: Vec<&Node> = node.child_nodes.iter().collect();
+    children.sort_by_key(|child| std::cmp::Reverse(child.n_sa));
+    let scores = children
+        .iter()
+        .map(|child| {
+            // w_sa は勝ち2 引き分け1  w_sa counts 2 for a win and 1 for a draw
+            let rate = match child.n_sa {
+                0 => 0.5,
+                n => child.w_sa as f64 / (2 * n) as f64,
+            };
+            (child.action, rate)
+        })
+        .collect();
+    let mut pv = Vec::new();
+    let mut current = &node;
+    while let Some(child) = current.child_nodes.iter().max_by_key(|child| child.n_sa) {
+        if child.n_sa == 0 {
+            break;
+        }
+        pv.push(child.action);
+        current = child;
+    }
+    (scores, node.n_sa as u64, pv, node.memory())
 }
 
 //# simple monte carlo
-fn primitive_montecarlo_s(node: &Node) -> Pmove {
+// 全ての手を同じ回数ずつロールアウトする  Every move gets the same number of rollouts
+// 各手の勝ち越し数の合計とロールアウトの回数  Sum of results per move and number of rounds
+fn primitive_montecarlo_values(node: &Node, limits: &Limits) -> (Vec<i32>, u32) {
+    let deadline = limits.time.map(|time| Instant::now() + time);
+    let next_nodes: Vec<Node> = node
+        .legal_actions
+        .iter()
+        .map(|action| node.next_node(action))
+        .collect();
+    // win +1 lose -1 draw 0
+    let mut values: Vec<i32> = vec![0; next_nodes.len()];
+    let mut rounds = 0;
+    while rounds < limits.playouts {
+        for (value, next_node) in values.iter_mut().zip(next_nodes.iter()) {
+            *value -= roolout(next_node);
+        }
+        rounds += 1;
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            break;
+        }
+    }
+    (values, rounds)
+}
+
+fn primitive_montecarlo_s(node: &Node, limits: &Limits) -> Pmove {
     if node.legal_actions.is_empty() {
         return Pmove::Passmove; //# pass check
     }
     if node.legal_actions.len() == 1 {
         return Pmove::Pvmove(node.legal_actions[0]);
     }
-    let mut values: Vec<i32> = Vec::new();
-    for &action in node.legal_actions.iter() {
-        // win +1 lose -1 draw 0
-        let mut total_value: i32 = 0;
-        let next_node = node.next_node(&action);
-        for _ in 0..200 {
-            total_value -= roolout(&next_node);
-        }
-        values.push(total_value);
-    }
+    let (values, _) = primitive_montecarlo_values(node, limits);
     let max_index = values
         .iter()
         .enumerate()
@@ -309,9 +402,28 @@ fn primitive_montecarlo_s(node: &Node) -> Pmove {
         .unwrap();
     Pmove::Pvmove(node.legal_actions[max_index])
 }
-pub fn primitive_montecarlo(black: u64, white: u64, _left: usize) -> Pmove {
+pub fn primitive_montecarlo(black: u64, white: u64, _left: usize, limits: &Limits) -> Pmove {
     let node = Node::new(TurnPlayer::Black, black, white, 0, 0);
-    primitive_montecarlo_s(&node)
+    primitive_montecarlo_s(&node, limits)
+}
+
+// 各手の勝率 (引き分けは半分) とロールアウトの回数
+// Win rate of each move (a draw counts half) and the number of rollouts
+pub fn primitive_montecarlo_scores(
+    black: u64,
+    white: u64,
+    _left: usize,
+    limits: &Limits,
+) -> (Vec<(u64, f64)>, u64) {
+    let node = Node::new(TurnPlayer::Black, black, white, 0, 0);
+    let (values, rounds) = primitive_montecarlo_values(&node, limits);
+    let scores = node
+        .legal_actions
+        .iter()
+        .zip(values)
+        .map(|(&action, value)| (action, (value as f64 / rounds.max(1) as f64 + 1.0) / 2.0))
+        .collect();
+    (scores, rounds as u64 * node.legal_actions.len() as u64)
 }
 
 fn roolout(node: &Node) -> i32 {
@@ -331,8 +443,22 @@ fn roolout(node: &Node) -> i32 {
             return - roolout(&node2);
         }
     }
-    let mut rng = thread_rng();
-    let next_action = node.legal_actions.choose(&mut rng).unwrap();
-    let next_node = node.next_node(next_action);
+    let next_action = with_random(|rng| *node.legal_actions.choose(rng).unwrap());
+    let next_node = node.next_node(&next_action);
         - roolout(&next_node)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pass_root() {
+        // B1 の手番は打てずにパス score_moves と同じく空  B1 has no move and must pass;
+        // the scores are empty, as in score_moves
+        let (b1, a1) = (1 << 62, 1 << 63);
+        let limits = Limits { playouts: 100, ..Limits::new(&crate::engine::Algorithm::Mcts) };
+        assert_eq!(mcts_scores(b1, a1, 62, &limits), (Vec::new(), 0, Vec::new(), 0));
+        assert_eq!(mcts(b1, a1, 62, &limits), Pmove::Passmove);
+    }
+}
@@ -0,0 +1,198 @@
+// NBoard protocol engine mode for Othello
+
+use std::io;
+use std::io::{BufRead, Write};
+use std::time::Duration;
+
+// from  bit_lib
+use crate::bit_lib::{can_locate, make_index, parse_move};
+use crate::board::Board;
+use crate::engine::Engine;
+use crate::position::Position;
+use crate::record::GameRecord;
+use crate::Pmove; // from main
+
+const PROTOCOL_VERSION: &str = "2";
+
+pub struct Nboard {
+    board: Board,
+    engine: Engine,
+}
+
+impl Nboard {
+    pub fn new(engine: Engine, start: Position) -> Self {
+        let board = Board::new(
+            (start.turn_player, "nboard".to_string(), "nboard".to_string()),
+            start.black,
+            start.white,
+        );
+        Nboard { board, engine }
+    }
+
+    // 1行のコマンドを実行し NBoard に返す行を返す  Execute one line and return the lines for NBoard
+    pub fn command(&mut self, line: &str) -> Result<Vec<String>, String> {
+        let line = line.trim();
+        let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        match name {
+            "nboard" => match rest {
+                PROTOCOL_VERSION => Ok(vec!["set myname Othello_Rust".to_string()]),
+                _ => Err(format!("unsupported protocol version: {}", rest)),
+            },
+            "set" => {
+                let (key, value) = rest.split_once(' ').unwrap_or((rest, ""));
+                match key {
+                    "depth" => {
+                        let depth: i32 = value
+                            .trim()
+                            .parse()
+                            .map_err(|_| format!("bad depth: {}", value))?;
+                        self.engine.limits.depth = depth.max(1);
+                    }
+                    // 失敗すれば盤面はそのまま  On error the board is left as it was
+                    "game" => GameRecord::parse(value)?.restore(&mut self.board, None)?,
+                    _ => {} // contempt など  contempt and others are ignored
+                }
+                Ok(Vec::new())
+            }
+            "move" => {
+                self.play(rest)?;
+                Ok(Vec::new())
+            }
+            "hint" => {
+                let n: usize = rest.parse().map_err(|_| format!("bad hint count: {}", rest))?;
+                Ok(self.hint(n))
+            }
+            "go" => {
+                let (first, second, left) = (self.board.first, self.board.second, self.board.left);
+                let mv = match self.engine.think(first, second, left) {
+                    Pmove::Pvmove(mv) => make_index(mv),
+                    _ => "PA".to_string(),
+                };
+                // 盤面は NBoard から move で届く  NBoard sends the move back with "move"
+                Ok(vec![format!("=== {}", mv)])
+            }
+            "ping" => Ok(vec![format!("pong {}", rest)]),
+            "learn" => Ok(vec!["learned".to_string()]),
+            "analyze" | "quit" => Ok(Vec::new()),
+            _ => Err(format!("unknown command: {}", name)),
+        }
+    }
+
+    // "F5", "F5/1.00/2.5" のような着手  A move with optional eval and time
+    fn play(&mut self, text: &str) -> Result<(), String> {
+        let mut fields = text.split('/');
+        let mv = fields.next().unwrap_or("");
+        let mv = parse_move(mv).ok_or_else(|| format!("bad move: {}", text))?;
+        let time = fields
+            .nth(1)
+            .and_then(|seconds| seconds.trim().parse::<f64>().ok())
+            .filter(|seconds| *seconds >= 0.0)
+            .map(Duration::from_secs_f64)
+            .unwrap_or(Duration::ZERO);
+        let legal = can_locate(self.board.first, self.board.second);
+        if mv != 0 && legal == 0 && self.board.is_can_play() {
+            self.board.update_board(Pmove::Passmove); // 省略されたパス  Omitted pass
+            return self.play(text);
+        }
+        if (mv == 0 && legal != 0) || (mv != 0 && legal & mv == 0) {
+            return Err(format!("illegal move: {}", text));
+        }
+        let pmove = if mv == 0 { Pmove::Passmove } else { Pmove::Pvmove(mv) };
+        self.board.update_board_timed(pmove, time);
+        Ok(())
+    }
+
+    // 上位 n 手の評価を search 行で返す  The best n moves as "search" lines
+    fn hint(&self, n: usize) -> Vec<String> {
+        let mut lines = vec!["status thinking".to_string()];
+        let (first, second, left) = (self.board.first, self.board.second, self.board.left);
+        if can_locate(first, second) != 0 {
+            let analysis = self.engine.analyze(first, second, left);
+            let depth = match analysis.depth {
+                0 => "0".to_string(),
+                depth if depth as usize >= left => "100%".to_string(),
+                depth => depth.to_string(),
+            };
+            for (mv, score) in analysis.moves.iter().take(n) {
+                lines.push(format!(
+                    "search {} {:.2} 0 {}",
+                    make_index(*mv),
+                    score.discs(),
+                    depth
+                ));
+            }
+        }
+        lines.push("status".to_string());
+        lines
+    }
+}
+
+// 標準入出力で NBoard プロトコルを処理する  Serve the NBoard protocol over stdin and stdout
+pub fn run(engine: Engine, start: Position) {
+    let mut nboard = Nboard::new(engine, start);
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    for line in stdin.lock().lines() {
+        let line = line.expect("Failed to read line");
+        match nboard.command(&line) {
+            Ok(lines) => {
+                for response in lines {
+                    writeln!(stdout, "{}", response).expect("Failed to write");
+                }
+            }
+            Err(e) => eprintln!("{}", e), // NBoard はエラー応答を持たない  No error replies
+        }
+        stdout.flush().expect("Failed to write");
+        if line.trim() == "quit" {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TurnPlayer;
+
+    fn new_nboard() -> Nboard {
+        Nboard::new(Engine::parse("alphabeta:depth=2").unwrap(), Position::default())
+    }
+
+    #[test]
+    fn test_nboard_session() {
+        let mut nboard = new_nboard();
+        assert_eq!(nboard.command("nboard 2"), Ok(vec!["set myname Othello_Rust".to_string()]));
+        assert!(nboard.command("nboard 1").is_err());
+        assert_eq!(nboard.command("set depth 4"), Ok(Vec::new()));
+        assert_eq!(nboard.engine.limits.depth, 4);
+        let ggf = "(;GM[Othello]PC[NBoard]PB[a]PW[b]RE[?]TI[5:00]TY[8]\
+                   BO[8 ---------------------------O*------*O--------------------------- *]\
+                   B[F5//1.5]W[D6];)";
+        assert_eq!(nboard.command(&format!("set game {}", ggf)), Ok(Vec::new()));
+        assert_eq!(nboard.board.turn_player, TurnPlayer::Black);
+        assert_eq!(nboard.command("move C3/0.50/2.25"), Ok(Vec::new()));
+        assert_eq!(nboard.board.history[2].time, Duration::from_millis(2250));
+        assert!(nboard.command("move C3").is_err());
+        // 再生できない棋譜では盤面を変えない  A game that fails to replay keeps the board
+        let bad = ggf.replace("W[D6]", "W[D6]B[A1]");
+        assert!(nboard.command(&format!("set game {}", bad)).is_err());
+        assert_eq!(nboard.board.history.len(), 3);
+        assert!(nboard.command("move PA").is_err());
+        assert_eq!(nboard.command("ping 7"), Ok(vec!["pong 7".to_string()]));
+        let go = nboard.command("go").unwrap();
+        assert!(go[0].starts_with("=== "));
+        assert_eq!(nboard.board.history.len(), 3);
+    }
+
+    #[test]
+    fn test_nboard_hint() {
+        // main.rs の Test 局面 黒10石勝ち  The Test position is a 10-disc win for Black
+        let start = Position::parse("0x6000100810120500 0x8EFCEFF76F6D3A3F b").unwrap();
+        let nboard = Nboard::new(Engine::new(crate::engine::Algorithm::AlphaBeta), start);
+        let lines = nboard.hint(2);
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("search ") && lines[1].ends_with(" 10.00 0 100%"));
+        assert_eq!(lines[3], "status");
+    }
+}
@@ -0,0 +1,206 @@
+// opening suites for engine matches
+// 決定的なエンジン同士でも違う対局になるように、いろいろな開始局面から対戦する
+// Matches start from many positions so that deterministic engines still play different games
+
+use std::collections::HashSet;
+use std::fs;
+
+// from  bit_lib
+use crate::bit_lib::{get_rev_pat, make_index, make_legal_actions, parse_moves};
+use crate::engine::Engine;
+use crate::position::Position;
+use crate::{TurnPlayer, BLACK_0, BOARDSIZE_SQ, WHITE_0}; // from lib
+
+// 互角とみなす評価の幅 読み切らない局面では評価値を石の重みで割った値で、石差ではない
+// Largest |score| counted as balanced. Short of a complete read this is the evaluation
+// divided by the stones weight (Score::discs), not a count of discs
+pub const DEFAULT_MARGIN: f64 = 2.0;
+// 互角かどうかを調べる読みの深さ  Search depth used to judge the balance
+pub const DEFAULT_DEPTH: i32 = 4;
+
+// 開始局面と名前 (着手列または局面の文字列)  A start position and its name
+#[derive(Clone, Debug, PartialEq)]
+pub struct Opening {
+    pub name: String,
+    pub position: Position,
+    // 初期局面からの着手 (パスは 0) 盤面で書いた局面では空
+    // Moves from the initial position (0 is a pass), empty for a position given as a board
+    pub moves: Vec<u64>,
+}
+
+impl Opening {
+    pub fn new(name: &str, position: Position) -> Self {
+        Opening {
+            name: name.to_string(),
+            position,
+            moves: Vec::new(),
+        }
+    }
+}
+
+// 1行に1つの開始局面 (--position と同じ形式)  One start position per line, in the
+// --position forms. '#' から行末まではコメント  Text after '#' is a comment
+pub fn parse_suite(text: &str) -> Result<Vec<Opening>, String> {
+    let mut suite = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let position = Position::parse(line).map_err(|e| format!("line {}: {}", number + 1, e))?;
+        let mut opening = Opening::new(line, position);
+        // 盤面と16進の局面は着手として読めない  Boards and hex positions do not parse as moves
+        opening.moves = parse_moves(line).unwrap_or_default();
+        suite.push(opening);
+    }
+    if suite.is_empty() {
+        return Err("no openings".to_string());
+    }
+    Ok(suite)
+}
+
+pub fn load_suite(path: &str) -> Result<Vec<Opening>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    parse_suite(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+// 初期局面から plies 手の全ての局面 (同じ局面は最初の着手列だけ, パスを含む手順は除く)
+// Every position plies moves from the start, the first move order of each transposition,
+// lines with a pass are skipped
+pub fn enumerate_openings(plies: usize) -> Vec<(Vec<u64>, Position)> {
+    fn walk(
+        my: u64,
+        en: u64,
+        moves: &mut Vec<u64>,
+        plies: usize,
+        seen: &mut HashSet<(u64, u64)>,
+        out: &mut Vec<(Vec<u64>, Position)>,
+    ) {
+        if moves.len() == plies {
+            // 手番側の石が先  my is the side to move
+            if seen.insert((my, en)) {
+                let turn_player = match plies.is_multiple_of(2) {
+                    true => TurnPlayer::Black,
+                    false => TurnPlayer::White,
+                };
+                let (black, white) = match turn_player {
+                    TurnPlayer::Black => (my, en),
+                    TurnPlayer::White => (en, my),
+                };
+                out.push((moves.clone(), Position { black, white, turn_player }));
+            }
+            return;
+        }
+        for mv in make_legal_actions(my, en) {
+            let rev = get_rev_pat(my, en, mv);
+            moves.push(mv);
+            walk(en ^ rev, my ^ (mv | rev), moves, plies, seen, out);
+            moves.pop();
+        }
+    }
+    let mut out = Vec::new();
+    walk(BLACK_0, WHITE_0, &mut Vec::new(), plies, &mut HashSet::new(), &mut out);
+    out
+}
+
+// 互角の plies 手の開始局面 judge の最善手の評価 (Score::discs) が margin 以内
+// Balanced openings of plies moves, where judge's best Score::discs is within margin
+pub fn generate_suite(plies: usize, judge: &Engine, margin: f64) -> Vec<(Opening, f64)> {
+    enumerate_openings(plies)
+        .into_iter()
+        .filter_map(|(moves, position)| {
+            let (first, second) = match position.turn_player {
+                TurnPlayer::Black => (position.black, position.white),
+                TurnPlayer::White => (position.white, position.black),
+            };
+            let left = BOARDSIZE_SQ - (first | second).count_ones() as usize;
+            let analysis = judge.analyze(first, second, left);
+            let score = analysis.moves.first()?.1.discs();
+            // 黒から見た評価  Score from Black's view
+            let score = if position.turn_player == TurnPlayer::Black { score } else { -score };
+            if score.abs() > margin {
+                return None;
+            }
+            let name: String =
+                moves.iter().map(|&mv| make_index(mv).to_ascii_lowercase()).collect();
+            let mut opening = Opening::new(&name, position);
+            opening.moves = moves;
+            Some((opening, score))
+        })
+        .collect()
+}
+
+// Othello openings <plies> <out> [--depth D] [--margin M]
+pub fn run_command(args: &[String]) -> Result<(), String> {
+    let usage = "usage: openings <plies> <out> [--depth D] [--margin M]";
+    let (Some(plies), Some(out)) = (args.first(), args.get(1)) else {
+        return Err(usage.to_string());
+    };
+    let plies: usize = plies.parse().map_err(|_| format!("bad plies: {}", plies))?;
+    let mut judge = Engine::parse("alphabeta")?;
+    judge.limits.depth = DEFAULT_DEPTH;
+    let mut margin = DEFAULT_MARGIN;
+    let mut options = args[2..].iter();
+    while let Some(option) = options.next() {
+        let value = options.next().ok_or(usage)?;
+        match option.as_str() {
+            "--depth" => {
+                judge.limits.depth = value.parse().map_err(|_| format!("bad depth: {}", value))?
+            }
+            "--margin" => {
+                margin = value.parse().map_err(|_| format!("bad margin: {}", value))?
+            }
+            _ => return Err(format!("unknown option: {}\n{}", option, usage)),
+        }
+    }
+    let suite = generate_suite(plies, &judge, margin);
+    let mut text = format!(
+        "# {} balanced {}-ply openings, {} margin {}\n",
+        suite.len(),
+        plies,
+        judge,
+        margin
+    );
+    for (opening, score) in &suite {
+        text.push_str(&format!("{}  # {:+.2}\n", opening.name, score));
+    }
+    fs::write(out, &text).map_err(|e| format!("{}: {}", out, e))?;
+    println!("{} openings: {}", suite.len(), out);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enumerate_openings() {
+        // 3手目までの局面の数 (転置をまとめたもの)  Distinct positions after 1 to 3 plies
+        assert_eq!(enumerate_openings(1).len(), 4);
+        assert_eq!(enumerate_openings(2).len(), 12);
+        assert_eq!(enumerate_openings(3).len(), 54);
+        let (moves, position) = &enumerate_openings(2)[0];
+        assert_eq!(moves.len(), 2);
+        assert_eq!(position.turn_player, TurnPlayer::Black);
+        let name: String = moves.iter().map(|&mv| make_index(mv)).collect();
+        assert_eq!(Position::parse(&name).unwrap(), *position);
+    }
+
+    #[test]
+    fn test_suite() {
+        let suite = parse_suite("# suite\nf5d6  # +0.00\n\nf5f6\n").unwrap();
+        assert_eq!(suite.len(), 2);
+        assert_eq!(suite[0].name, "f5d6");
+        assert_eq!(suite[1].position.turn_player, TurnPlayer::Black);
+        assert_eq!(suite[1].moves, parse_moves("f5f6").unwrap());
+        let board = parse_suite(&Position::default().to_string()).unwrap();
+        assert!(board[0].moves.is_empty());
+        assert!(parse_suite("f5f5").unwrap_err().starts_with("line 1"));
+        assert!(parse_suite("# empty\n").is_err());
+        let judge = Engine::parse("alphabeta:depth=2").unwrap();
+        let suite = generate_suite(2, &judge, 64.0);
+        assert_eq!(suite.len(), 12);
+        assert_eq!(suite[0].0.moves.len(), 2);
+        assert!(generate_suite(2, &judge, 0.0).len() < suite.len());
+    }
+}
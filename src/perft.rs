@@ -0,0 +1,266 @@
+// perft: leaf counts of the move tree, to check and time the move generator
+// パスは1手として数え、終局した局面は深さが残っていても葉として1と数える
+// A pass counts as a ply, and a finished game is one leaf even before the full depth
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// from  bit_lib
+use crate::bit_lib::{can_locate, can_locate_reference, get_rev_pat, get_rev_pat_reference};
+use crate::bit_lib::make_index;
+use crate::position::Position;
+use crate::{TurnPlayer, BLACK_0, WHITE_0}; // from lib
+
+// ベンチマークに使うランダムな対局の数  Random games whose positions are benchmarked
+const BENCH_GAMES: usize = 200;
+// ベンチマークの既定の繰り返し回数  Default rounds over the benchmark positions
+const BENCH_ROUNDS: u32 = 2000;
+
+// 手番側 my から depth 手先の葉の数  Leaves depth plies ahead, my to move
+pub fn perft(my: u64, en: u64, depth: u32) -> u64 {
+    count(my, en, depth, &can_locate, &get_rev_pat)
+}
+
+// 元の着手生成での perft (速さの比較用)  perft with the original move generator,
+// to compare speeds
+pub fn perft_reference(my: u64, en: u64, depth: u32) -> u64 {
+    count(my, en, depth, &can_locate_reference, &get_rev_pat_reference)
+}
+
+fn count<M, F>(my: u64, en: u64, depth: u32, mobility: &M, flips: &F) -> u64
+where
+    M: Fn(u64, u64) -> u64,
+    F: Fn(u64, u64, u64) -> u64,
+{
+    if depth == 0 {
+        return 1;
+    }
+    let mut moves = mobility(my, en);
+    if moves == 0 {
+        return match mobility(en, my) {
+            0 => 1, // 終局  Game over
+            _ => count(en, my, depth - 1, mobility, flips),
+        };
+    }
+    if depth == 1 {
+        return moves.count_ones() as u64;
+    }
+    let mut nodes = 0;
+    while moves != 0 {
+        let mv = moves & moves.wrapping_neg();
+        moves ^= mv;
+        let rev = flips(my, en, mv);
+        nodes += count(en ^ rev, my ^ (mv | rev), depth - 1, mobility, flips);
+    }
+    nodes
+}
+
+// 1手目ごとの葉の数 (パスは 0)  Leaves under each first move, 0 for a pass
+pub fn divide(my: u64, en: u64, depth: u32) -> Vec<(u64, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    let mut moves = can_locate(my, en);
+    if moves == 0 {
+        return match can_locate(en, my) {
+            0 => Vec::new(),
+            _ => vec![(0, perft(en, my, depth - 1))],
+        };
+    }
+    let mut out = Vec::new();
+    while moves != 0 {
+        let mv = moves & moves.wrapping_neg();
+        moves ^= mv;
+        let rev = get_rev_pat(my, en, mv);
+        out.push((mv, perft(en ^ rev, my ^ (mv | rev), depth - 1)));
+    }
+    out
+}
+
+// 決まった種のランダムな対局に現れた局面 (手番側, 相手) 合法手のある局面だけ
+// Positions (to move, opponent) with a legal move, from random games with a fixed seed
+pub fn bench_positions(games: usize) -> Vec<(u64, u64)> {
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut positions = Vec::new();
+    for _ in 0..games {
+        let (mut my, mut en) = (BLACK_0, WHITE_0);
+        loop {
+            let mut moves = can_locate(my, en);
+            if moves == 0 {
+                if can_locate(en, my) == 0 {
+                    break;
+                }
+                (my, en) = (en, my);
+                continue;
+            }
+            positions.push((my, en));
+            for _ in 0..rng.gen_range(0..moves.count_ones()) {
+                moves &= moves - 1;
+            }
+            let mv = moves & moves.wrapping_neg();
+            let rev = get_rev_pat(my, en, mv);
+            (my, en) = (en ^ rev, my ^ (mv | rev));
+        }
+    }
+    positions
+}
+
+// 合法手の生成を rounds 回繰り返した1回あたりのナノ秒  Nanoseconds per mobility call
+fn bench_mobility(positions: &[(u64, u64)], rounds: u32, mobility: fn(u64, u64) -> u64) -> f64 {
+    let start = Instant::now();
+    let mut sum = 0u64;
+    for _ in 0..rounds {
+        for &(my, en) in positions {
+            sum = sum.wrapping_add(mobility(black_box(my), black_box(en)));
+        }
+    }
+    black_box(sum);
+    start.elapsed().as_nanos() as f64 / (rounds as f64 * positions.len() as f64)
+}
+
+// 全ての合法手の返る石を rounds 回求めた1手あたりのナノ秒  Nanoseconds per flip call,
+// over every legal move of every position
+fn bench_flips(positions: &[(u64, u64)], rounds: u32, flips: fn(u64, u64, u64) -> u64) -> f64 {
+    let moves: Vec<(u64, u64, u64)> = positions
+        .iter()
+        .flat_map(|&(my, en)| {
+            let legal = can_locate(my, en);
+            (0..64).map(move |i| 1u64 << i).filter(move |mv| legal & mv != 0).map(move |mv| (my, en, mv))
+        })
+        .collect();
+    let start = Instant::now();
+    let mut sum = 0u64;
+    for _ in 0..rounds {
+        for &(my, en, mv) in &moves {
+            sum = sum.wrapping_add(flips(black_box(my), black_box(en), black_box(mv)));
+        }
+    }
+    black_box(sum);
+    start.elapsed().as_nanos() as f64 / (rounds as f64 * moves.len() as f64)
+}
+
+// Othello perft bench [rounds]  合法手と返る石を別々に元の実装と比べる
+// Compare mobility and flips with the original implementations, each on its own
+fn run_bench(args: &[String]) -> Result<(), String> {
+    let rounds = match args.first() {
+        Some(rounds) => rounds.parse().map_err(|_| format!("bad rounds: {}", rounds))?,
+        None => BENCH_ROUNDS,
+    };
+    let positions = bench_positions(BENCH_GAMES);
+    println!("{} positions from {} random games, {} rounds", positions.len(), BENCH_GAMES, rounds);
+    let mobility = bench_mobility(&positions, rounds, can_locate);
+    let mobility_reference = bench_mobility(&positions, rounds, can_locate_reference);
+    println!(
+        "mobility: {:6.2} ns  reference {:6.2} ns  x{:.2}",
+        mobility,
+        mobility_reference,
+        mobility_reference / mobility
+    );
+    let flips = bench_flips(&positions, rounds, get_rev_pat);
+    let flips_reference = bench_flips(&positions, rounds, get_rev_pat_reference);
+    println!(
+        "flips:    {:6.2} ns  reference {:6.2} ns  x{:.2}",
+        flips,
+        flips_reference,
+        flips_reference / flips
+    );
+    Ok(())
+}
+
+// Othello perft <depth> [--divide] [--compare]
+// Othello perft bench [rounds]
+pub fn run_command(args: &[String], start: Position) -> Result<(), String> {
+    let usage = "usage: perft <depth> [--divide] [--compare] | perft bench [rounds]";
+    if args.first().map(|s| s.as_str()) == Some("bench") {
+        return run_bench(&args[1..]);
+    }
+    let depth = args.first().ok_or(usage)?;
+    let depth: u32 = depth.parse().map_err(|_| format!("bad depth: {}", depth))?;
+    let (mut show_divide, mut compare) = (false, false);
+    for arg in &args[1..] {
+        match arg.as_str() {
+            "--divide" => show_divide = true,
+            "--compare" => compare = true,
+            _ => return Err(format!("unknown option: {}\n{}", arg, usage)),
+        }
+    }
+    let (my, en) = match start.turn_player {
+        TurnPlayer::Black => (start.black, start.white),
+        TurnPlayer::White => (start.white, start.black),
+    };
+    if show_divide {
+        let mut total = 0;
+        for (mv, nodes) in divide(my, en, depth) {
+            println!("{}: {}", make_index(mv).to_ascii_lowercase(), nodes);
+            total += nodes;
+        }
+        println!("total: {}", total);
+        return Ok(());
+    }
+    // 1秒あたりの局面数  Nodes per second
+    let timed = |perft: fn(u64, u64, u32) -> u64, depth| {
+        let start = Instant::now();
+        let nodes = perft(my, en, depth);
+        let seconds = start.elapsed().as_secs_f64();
+        (nodes, seconds, nodes as f64 / seconds.max(1e-9))
+    };
+    for depth in 1..=depth {
+        let (nodes, seconds, speed) = timed(perft, depth);
+        print!("depth {:2}: {:14} nodes {:9.3}s {:12.0} nodes/s", depth, nodes, seconds, speed);
+        if compare {
+            let (reference_nodes, _, reference_speed) = timed(perft_reference, depth);
+            if reference_nodes != nodes {
+                return Err(format!("reference move generator counts {}", reference_nodes));
+            }
+            print!("  reference {:12.0} nodes/s  x{:.2}", reference_speed, speed / reference_speed);
+        }
+        println!();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_perft() {
+        // 初期局面からの既知の値 (11手 212258800 は release で確認)
+        // Reference counts from the initial position; depth 11 checked in release builds
+        let expected = [1, 4, 12, 56, 244, 1396, 8200, 55092, 390216, 3005288];
+        for (depth, &nodes) in expected.iter().enumerate() {
+            assert_eq!(perft(BLACK_0, WHITE_0, depth as u32), nodes, "depth {}", depth);
+        }
+        assert_eq!(perft_reference(BLACK_0, WHITE_0, 6), 8200);
+        let total: u64 = divide(BLACK_0, WHITE_0, 5).iter().map(|&(_, nodes)| nodes).sum();
+        assert_eq!(total, 1396);
+        // 黒だけの盤は終局  A board of one colour is a finished game
+        assert_eq!(perft(BLACK_0 | WHITE_0, 0, 3), 1);
+        // B1 の手番は打てずにパス, A1 の側が C1 に打つ  B1 passes, then A1's side plays C1
+        let (a1, b1) = (1 << 63, 1 << 62);
+        assert_eq!(perft(b1, a1, 1), 1);
+        assert_eq!(perft(b1, a1, 2), 1);
+        assert_eq!(divide(b1, a1, 2), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn test_bench() {
+        let positions = bench_positions(2);
+        assert!(positions.len() > 100);
+        assert_eq!(positions, bench_positions(2));
+        assert!(positions.iter().all(|&(my, en)| can_locate(my, en) != 0));
+        assert!(bench_mobility(&positions, 1, can_locate) > 0.0);
+        assert!(bench_flips(&positions, 1, get_rev_pat) > 0.0);
+    }
+
+    // 時間がかかるので cargo test -- --ignored で実行する  Slow, run with cargo test -- --ignored
+    #[test]
+    #[ignore]
+    fn test_perft_deep() {
+        assert_eq!(perft(BLACK_0, WHITE_0, 10), 24571284);
+        assert_eq!(perft_reference(BLACK_0, WHITE_0, 9), 3005288);
+    }
+}
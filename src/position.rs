@@ -0,0 +1,214 @@
+// position parser and serializer for Othello
+
+use std::fmt;
+
+use crate::bit_lib::parse_moves;
+use crate::board::replay_moves;
+use crate::{TurnPlayer, BLACK_0, WHITE_0}; // from main
+
+// 局面と手番  A position and the side to move
+#[derive(Clone, Debug, PartialEq)]
+pub struct Position {
+    pub black: u64,
+    pub white: u64,
+    pub turn_player: TurnPlayer,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Position {
+            black: BLACK_0,
+            white: WHITE_0,
+            turn_player: TurnPlayer::Black,
+        }
+    }
+}
+
+impl Position {
+    // 次の形式を受け付ける  Accepted forms:
+    //   "---------------------------OX------XO--------------------------- X"  64 squares + side
+    //   "0x0000000810000000 0x0000001008000000 b"  hex bitboards black, white + side
+    //   "f5d6c3"  moves from the initial position
+    // 手番を省略すると黒  The side to move defaults to Black
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err("empty position".to_string());
+        }
+        if text.starts_with("0x") || text.starts_with("0X") {
+            return Position::parse_hex(text);
+        }
+        // 空白で区切った最後の "X" や "black" は手番  A trailing word such as "X" or "black"
+        // after whitespace is the side to move
+        let (body, side) = match text.rsplit_once(char::is_whitespace) {
+            Some((body, side)) if parse_side(side).is_ok() => (body, Some(side.to_string())),
+            _ => (text, None),
+        };
+        let squares: String = body.chars().filter(|c| !c.is_whitespace()).collect();
+        // 32手の棋譜も64文字なので、盤面の文字だけのときに盤面とする
+        // A 32-move transcript is also 64 characters, so only board characters make a board
+        let is_board = squares.chars().all(|c| "*XxBbOoWw-.".contains(c));
+        let count = squares.chars().count();
+        if is_board && (count == 64 || (count == 65 && side.is_none())) {
+            let mut chars = squares.chars();
+            let board: String = chars.by_ref().take(64).collect();
+            let (black, white) = parse_squares(&board)?;
+            let turn_player = match side.or_else(|| chars.next().map(String::from)) {
+                Some(side) => parse_side(&side)?,
+                None => TurnPlayer::Black,
+            };
+            return Ok(Position {
+                black,
+                white,
+                turn_player,
+            });
+        }
+        let moves = parse_moves(text).ok_or_else(|| format!("unknown position: {}", text))?;
+        let (black, white, turn_player) = replay_moves(&moves)?;
+        Ok(Position {
+            black,
+            white,
+            turn_player,
+        })
+    }
+
+    fn parse_hex(text: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = text
+            .split(|c: char| c.is_whitespace() || c == ',' || c == ':')
+            .filter(|s| !s.is_empty())
+            .collect();
+        if fields.len() < 2 || fields.len() > 3 {
+            return Err(format!("expected <black> <white> [side]: {}", text));
+        }
+        let hex = |s: &str| -> Result<u64, String> {
+            let digits = s.trim_start_matches("0x").trim_start_matches("0X").replace('_', "");
+            u64::from_str_radix(&digits, 16).map_err(|_| format!("bad bitboard: {}", s))
+        };
+        let (black, white) = (hex(fields[0])?, hex(fields[1])?);
+        if black & white != 0 {
+            return Err("black and white overlap".to_string());
+        }
+        let turn_player = match fields.get(2) {
+            Some(side) => parse_side(side)?,
+            None => TurnPlayer::Black,
+        };
+        Ok(Position {
+            black,
+            white,
+            turn_player,
+        })
+    }
+
+    // 16進のビットボード表記  Hex bitboard form
+    pub fn to_hex(&self) -> String {
+        let side = if self.turn_player == TurnPlayer::Black { 'b' } else { 'w' };
+        format!("0x{:016x} 0x{:016x} {}", self.black, self.white, side)
+    }
+}
+
+// 64文字の盤面と手番 "X" または "O"  64 squares then the side to move
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let side = if self.turn_player == TurnPlayer::Black { 'X' } else { 'O' };
+        write!(f, "{} {}", format_squares(self.black, self.white, 'X', 'O'), side)
+    }
+}
+
+// A1, B1, ..., H8 の順の64文字  64 characters from A1 to H8
+pub fn format_squares(black: u64, white: u64, black_char: char, white_char: char) -> String {
+    (0..64)
+        .map(|i| {
+            let bit = 0x8000_0000_0000_0000u64 >> i;
+            if black & bit != 0 {
+                black_char
+            } else if white & bit != 0 {
+                white_char
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+// 'X' '*' 黒, 'O' 白, '-' '.' 空き の64文字  64 squares: X or * black, O white, - or . empty
+pub fn parse_squares(squares: &str) -> Result<(u64, u64), String> {
+    if squares.chars().count() != 64 {
+        return Err(format!("expected 64 squares: {}", squares));
+    }
+    let (mut black, mut white) = (0, 0);
+    for (i, c) in squares.chars().enumerate() {
+        let bit = 0x8000_0000_0000_0000u64 >> i;
+        match c {
+            '*' | 'X' | 'x' | 'B' | 'b' => black |= bit,
+            'O' | 'o' | 'W' | 'w' => white |= bit,
+            '-' | '.' => {}
+            _ => return Err(format!("bad square: {}", c)),
+        }
+    }
+    Ok((black, white))
+}
+
+fn parse_side(side: &str) -> Result<TurnPlayer, String> {
+    match side {
+        "X" | "x" | "*" | "B" | "b" | "black" | "Black" => Ok(TurnPlayer::Black),
+        "O" | "o" | "W" | "w" | "white" | "White" => Ok(TurnPlayer::White),
+        _ => Err(format!("bad side to move: {}", side)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bit_lib::{get_rev_pat, make_index, make_legal_actions};
+
+    #[test]
+    fn test_position_round_trip() {
+        let start = Position::default();
+        let text = start.to_string();
+        assert_eq!(
+            text,
+            "---------------------------OX------XO--------------------------- X"
+        );
+        assert_eq!(Position::parse(&text), Ok(start.clone()));
+        assert_eq!(Position::parse(&start.to_hex()), Ok(start.clone()));
+        assert_eq!(Position::parse("0x0000000810000000,0x0000001008000000"), Ok(start));
+        let white = Position {
+            black: 0x6000_1008_1012_0500,
+            white: 0x8EFC_EFF7_6F6D_3A3F,
+            turn_player: TurnPlayer::White,
+        };
+        assert_eq!(Position::parse(&white.to_string()), Ok(white.clone()));
+        // 手番は "black" "white" とも書ける  The side to move can be a word
+        let board = format_squares(white.black, white.white, 'X', 'O');
+        assert_eq!(Position::parse(&format!("{} white", board)), Ok(white.clone()));
+        let black = Position { turn_player: TurnPlayer::Black, ..white.clone() };
+        assert_eq!(Position::parse(&format!("{}  Black", board)), Ok(black));
+        assert!(Position::parse(&format!("{} red", board)).is_err());
+        assert_eq!(Position::parse(&white.to_hex()), Ok(white));
+    }
+
+    #[test]
+    fn test_position_from_moves() {
+        let position = Position::parse("f5d6").unwrap();
+        assert_eq!(position.turn_player, TurnPlayer::Black);
+        assert_eq!((position.black | position.white).count_ones(), 6);
+        assert!(Position::parse("f5f5").is_err());
+        assert!(Position::parse("0x1 0x1").is_err());
+        assert!(Position::parse("-----").is_err());
+        // 32手 (64文字) の棋譜  A 32-move transcript of 64 characters
+        let (mut black, mut white, mut transcript) = (BLACK_0, WHITE_0, String::new());
+        for ply in 0..32 {
+            let (my, en) = if ply % 2 == 0 { (black, white) } else { (white, black) };
+            let actions = make_legal_actions(my, en);
+            let mv = actions[ply % actions.len()];
+            let rev = get_rev_pat(my, en, mv);
+            let (my, en) = (my ^ (mv | rev), en ^ rev);
+            (black, white) = if ply % 2 == 0 { (my, en) } else { (en, my) };
+            transcript.push_str(&make_index(mv).to_ascii_lowercase());
+        }
+        assert_eq!(transcript.len(), 64);
+        let position = Position::parse(&transcript).unwrap();
+        assert_eq!((position.black, position.white), (black, white));
+        assert_eq!(position.turn_player, TurnPlayer::Black);
+    }
+}
@@ -0,0 +1,318 @@
+// game records for Othello: f5d6 move transcripts and GGF (Generic Game Format)
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+// from  bit_lib
+use crate::bit_lib::{can_locate, make_index, parse_move, parse_moves};
+use crate::board::Board;
+use crate::position::{format_squares, parse_squares};
+use crate::{Pmove, TurnPlayer, BLACK_0, WHITE_0}; // from main
+
+// 読み込んだ対局  A loaded game: start position, players and moves (0 is a pass)
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameRecord {
+    pub black: u64,
+    pub white: u64,
+    pub turn_player: TurnPlayer,
+    pub black_name: String,
+    pub white_name: String,
+    pub moves: Vec<(u64, Duration)>,
+}
+
+impl GameRecord {
+    // GGF "(;GM[Othello]...;)" または "f5d6c3..." の棋譜  GGF or a plain move transcript
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if text.starts_with("(;") {
+            return GameRecord::parse_ggf(text);
+        }
+        let moves = parse_moves(text).ok_or_else(|| "bad transcript".to_string())?;
+        Ok(GameRecord {
+            black: BLACK_0,
+            white: WHITE_0,
+            turn_player: TurnPlayer::Black,
+            black_name: String::new(),
+            white_name: String::new(),
+            moves: moves.into_iter().map(|mv| (mv, Duration::ZERO)).collect(),
+        })
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        GameRecord::parse(&text)
+    }
+
+    fn parse_ggf(text: &str) -> Result<Self, String> {
+        let mut record = GameRecord {
+            black: BLACK_0,
+            white: WHITE_0,
+            turn_player: TurnPlayer::Black,
+            black_name: String::new(),
+            white_name: String::new(),
+            moves: Vec::new(),
+        };
+        for (tag, value) in ggf_tags(text) {
+            match tag.as_str() {
+                "PB" => record.black_name = value,
+                "PW" => record.white_name = value,
+                "BO" => {
+                    let fields: Vec<&str> = value.split_whitespace().collect();
+                    if fields.len() < 3 || fields[0] != "8" {
+                        return Err(format!("bad BO: {}", value));
+                    }
+                    let squares: String = fields[1..fields.len() - 1].concat();
+                    (record.black, record.white) = parse_squares(&squares)?;
+                    record.turn_player = match fields[fields.len() - 1] {
+                        "*" => TurnPlayer::Black,
+                        "O" => TurnPlayer::White,
+                        side => return Err(format!("bad side to move: {}", side)),
+                    };
+                }
+                "B" | "W" => {
+                    let mut fields = value.split('/');
+                    let mv = fields.next().unwrap_or("");
+                    let mv = parse_move(mv).ok_or_else(|| format!("bad move: {}", value))?;
+                    let time = fields
+                        .nth(1)
+                        .and_then(parse_seconds)
+                        .map(Duration::from_secs_f64)
+                        .unwrap_or(Duration::ZERO);
+                    record.moves.push((mv, time));
+                }
+                _ => {}
+            }
+        }
+        Ok(record)
+    }
+
+    // 盤面に対局を再生し upto 手目 (パスを除く) まで戻す 残りは redo できる
+    // Replay the game on the board, then step back to move `upto` (passes not counted).
+    // The remaining moves stay available to redo.
+    // 途中で失敗しても盤面は変えない  A failed replay leaves the board unchanged
+    pub fn restore(&self, board: &mut Board, upto: Option<usize>) -> Result<(), String> {
+        let mut replay = board.clone();
+        self.replay(&mut replay, upto)?;
+        *board = replay;
+        Ok(())
+    }
+
+    fn replay(&self, board: &mut Board, upto: Option<usize>) -> Result<(), String> {
+        board.reset(self.black, self.white, self.turn_player.clone());
+        for &(mv, time) in &self.moves {
+            if !board.is_can_play() {
+                return Err(format!("game is over before {}", make_index(mv)));
+            }
+            if mv != 0 && can_locate(board.first, board.second) == 0 {
+                board.update_board(Pmove::Passmove); // 省略されたパス  Omitted pass
+            }
+            if mv == 0 {
+                if can_locate(board.first, board.second) != 0 {
+                    return Err("illegal pass".to_string());
+                }
+                board.update_board_timed(Pmove::Passmove, time);
+            } else if can_locate(board.first, board.second) & mv == 0 {
+                return Err(format!("illegal move {}", make_index(mv)));
+            } else {
+                board.update_board_timed(Pmove::Pvmove(mv), time);
+            }
+        }
+        if let Some(upto) = upto {
+            while count_moves(board) > upto {
+                board.undo();
+            }
+        }
+        Ok(())
+    }
+}
+
+// パスを除いた着手数  Number of moves played, passes not counted
+pub fn count_moves(board: &Board) -> usize {
+    board
+        .history
+        .iter()
+        .filter(|record| record.pmove != Pmove::Passmove)
+        .count()
+}
+
+// パスを "pa" とした f5d6 形式の棋譜  Move transcript with "pa" for a pass
+pub fn to_transcript(board: &Board) -> String {
+    board
+        .history
+        .iter()
+        .map(|record| match record.pmove {
+            Pmove::Pvmove(mv) => make_index(mv).to_ascii_lowercase(),
+            _ => "pa".to_string(),
+        })
+        .collect()
+}
+
+// GGF の対局記録  The game as a GGF record
+pub fn to_ggf(board: &Board) -> String {
+    let (black, white, turn_player) = match board.history.first() {
+        Some(record) => (record.black, record.white, record.turn_player.clone()),
+        None => (board.black, board.white, board.turn_player.clone()),
+    };
+    let squares = format_squares(black, white, '*', 'O');
+    let side = if turn_player == TurnPlayer::Black { '*' } else { 'O' };
+    let result = if board.is_can_play() {
+        "?".to_string()
+    } else {
+        let score = board.black.count_ones() as i32 - board.white.count_ones() as i32;
+        format!("{:+}.000", score)
+    };
+    let time_used = |color: TurnPlayer| -> f64 {
+        board
+            .history
+            .iter()
+            .filter(|record| record.turn_player == color)
+            .map(|record| record.time.as_secs_f64())
+            .sum()
+    };
+    let mut ggf = format!(
+        "(;GM[Othello]PC[Othello_Rust]PB[{}]PW[{}]RE[{}]TY[8]C[time used: black {:.2} white {:.2}]\
+         BO[8 {} {}]",
+        ggf_escape(&board.first_player),
+        ggf_escape(&board.second_player),
+        result,
+        time_used(TurnPlayer::Black),
+        time_used(TurnPlayer::White),
+        squares,
+        side
+    );
+    for record in &board.history {
+        let color = if record.turn_player == TurnPlayer::Black { "B" } else { "W" };
+        let mv = match record.pmove {
+            Pmove::Pvmove(mv) => make_index(mv),
+            _ => "PA".to_string(),
+        };
+        ggf.push_str(&format!("{}[{}//{:.2}]", color, mv, record.time.as_secs_f64()));
+    }
+    ggf.push_str(";)");
+    ggf
+}
+
+// <path>.txt に棋譜を、<path>.ggf に GGF を書く  Write <path>.txt and <path>.ggf
+pub fn save_game(board: &Board, path: &str) -> Result<(), String> {
+    let path = Path::new(path);
+    for (extension, text) in [("txt", to_transcript(board)), ("ggf", to_ggf(board))] {
+        let file = path.with_extension(extension);
+        fs::write(&file, text + "\n").map_err(|e| format!("{}: {}", file.display(), e))?;
+        println!("保存しました: {}", file.display());
+    }
+    Ok(())
+}
+
+// "12.5" または "1:02.5" の秒数  Seconds written as "12.5" or "1:02.5"
+fn parse_seconds(text: &str) -> Option<f64> {
+    text.split(':')
+        .try_fold(0.0, |total, part| part.trim().parse::<f64>().ok().map(|v| total * 60.0 + v))
+}
+
+fn ggf_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace(']', "\\]")
+}
+
+// "XX[value]" の組を順に取り出す  Tags in order of appearance
+fn ggf_tags(text: &str) -> Vec<(String, String)> {
+    let mut tags = Vec::new();
+    let mut name = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_uppercase() {
+            name.push(c);
+        } else if c == '[' {
+            let mut value = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next()),
+                    ']' => break,
+                    _ => value.push(c),
+                }
+            }
+            tags.push((std::mem::take(&mut name), value));
+        } else {
+            name.clear();
+        }
+    }
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_board() -> Board {
+        Board::new((TurnPlayer::Black, "X".to_string(), "O".to_string()), BLACK_0, WHITE_0)
+    }
+
+    #[test]
+    fn test_transcript_round_trip() {
+        let record = GameRecord::parse("f5d6c3d3c4").unwrap();
+        let mut board = new_board();
+        record.restore(&mut board, None).unwrap();
+        assert_eq!(to_transcript(&board), "f5d6c3d3c4");
+        record.restore(&mut board, Some(2)).unwrap();
+        assert_eq!(to_transcript(&board), "f5d6");
+        assert_eq!(board.turn_player, TurnPlayer::Black);
+        assert!(board.redo().is_some());
+        assert!(GameRecord::parse("f5f5").unwrap().restore(&mut board, None).is_err());
+        // パスは "pa" として残る  A pass stays in the transcript as "pa"
+        let transcript = "c4c3c2b2e6c5c6f6f5f4g7b5a1b4b3d7a6d2d6a3d3f7g4h4g5e3d8g6g3a4f8d1b6f2g2\
+                          h6e2a5h5h7h8a7e7g1h2e1h3b1c1f3b7c7f1e8g8c8h1pab8a8";
+        GameRecord::parse(transcript).unwrap().restore(&mut board, None).unwrap();
+        assert_eq!(to_transcript(&board), transcript);
+        assert!(!board.is_can_play());
+        let without_pass = transcript.replace("pa", "");
+        GameRecord::parse(&without_pass).unwrap().restore(&mut board, None).unwrap();
+        assert_eq!(to_transcript(&board), transcript);
+    }
+
+    #[test]
+    fn test_failed_restore_keeps_board() {
+        let mut board = new_board();
+        GameRecord::parse("f5d6").unwrap().restore(&mut board, None).unwrap();
+        // 3手目 c3 までは合法, 4手目 a1 は打てない  Legal up to c3, then a1 is illegal
+        // 同時に走る cargo test とぶつからない名前  A name no concurrent test run shares
+        let name = format!("othello_test_failed_restore_{}.txt", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, "f5d6c3a1").unwrap();
+        let record = GameRecord::load(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(record.restore(&mut board, None).is_err());
+        assert_eq!(to_transcript(&board), "f5d6");
+        assert_eq!(board.turn_player, TurnPlayer::Black);
+    }
+
+    #[test]
+    fn test_ggf_round_trip() {
+        let mut board = new_board();
+        GameRecord::parse("f5d6c3").unwrap().restore(&mut board, None).unwrap();
+        let d3 = Pmove::Pvmove(parse_move("d3").unwrap());
+        board.update_board_timed(d3, Duration::from_millis(1500));
+        let ggf = to_ggf(&board);
+        assert!(ggf.contains("PB[X]PW[O]RE[?]"));
+        assert!(ggf.contains("B[F5//0.00]W[D6//0.00]B[C3//0.00]W[D3//1.50]"));
+        let record = GameRecord::parse(&ggf).unwrap();
+        assert_eq!((record.black, record.white), (BLACK_0, WHITE_0));
+        assert_eq!(record.black_name, "X");
+        assert_eq!(record.moves.len(), 4);
+        assert_eq!(record.moves[3].1, Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn test_ggf_passes_and_start_position() {
+        // 白の手番から始まる局面 白はパス  White to move must pass
+        let ggf = "(;GM[Othello]PB[a]PW[b]BO[8 *O------ -------- -------- -------- \
+                   -------- -------- -------- -------- O]W[PA]B[C1];)";
+        let record = GameRecord::parse(ggf).unwrap();
+        assert_eq!(record.turn_player, TurnPlayer::White);
+        let mut board = new_board();
+        record.restore(&mut board, None).unwrap();
+        assert_eq!(board.black, 0xe000_0000_0000_0000);
+        assert_eq!(board.history.len(), 2);
+        assert!(to_ggf(&board).contains("RE[+3.000]"));
+        assert!(to_ggf(&board).contains("W[PA//0.00]B[C1//0.00]"));
+    }
+}
@@ -0,0 +1,219 @@
+// machine-readable match results: CSV, JSON and one GGF file per game
+
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+// from  bit_lib
+use crate::bit_lib::make_index;
+use crate::record::to_ggf;
+use crate::tournament::{GameResult, Tournament};
+use crate::{Pmove, TurnPlayer}; // from lib
+
+const CSV_HEADER: &str = "game,black,white,black_settings,white_settings,seed,opening,\
+                          opening_plies,moves,black_discs,white_discs,winner,lost_on_time,black_time,white_time,move_times";
+
+// 1局の記録 着手はパスを "pa" とした f5d6 形式, 時間は秒
+// 着手は開始局面までの opening_plies 手を含む (考慮時間は 0)
+// One game's record: moves in f5d6 form with "pa" for a pass, times in seconds.
+// The moves start with the opening_plies moves of the opening, timed as 0
+pub fn game_json(number: usize, tournament: &Tournament, result: &GameResult) -> Value {
+    let (black, white) = (&tournament.entrants[result.black], &tournament.entrants[result.white]);
+    let opening = &tournament.openings[result.opening];
+    let played = result.board.history.iter().map(|record| match record.pmove {
+        Pmove::Pvmove(mv) => mv,
+        _ => 0,
+    });
+    let moves: Vec<String> = opening
+        .moves
+        .iter()
+        .copied()
+        .chain(played)
+        .map(|mv| match mv {
+            0 => "pa".to_string(),
+            mv => make_index(mv).to_ascii_lowercase(),
+        })
+        .collect();
+    let times: Vec<f64> = opening
+        .moves
+        .iter()
+        .map(|_| 0.0)
+        .chain(result.board.history.iter().map(|record| record.time.as_secs_f64()))
+        .collect();
+    json!({
+        "game": number + 1,
+        "black": black.name,
+        "white": white.name,
+        "black_settings": black.engine.to_string(),
+        "white_settings": white.engine.to_string(),
+        "seed": result.seed,
+        "opening": opening.name,
+        "opening_plies": opening.moves.len(),
+        "moves": moves,
+        "black_discs": result.board.black.count_ones(),
+        "white_discs": result.board.white.count_ones(),
+        "winner": color_name(result.winner().as_ref()),
+        "lost_on_time": result.flagged.as_ref().map(|color| color_name(Some(color))),
+        "black_time": result.time_used(&TurnPlayer::Black).as_secs_f64(),
+        "white_time": result.time_used(&TurnPlayer::White).as_secs_f64(),
+        "move_times": times,
+    })
+}
+
+fn color_name(color: Option<&TurnPlayer>) -> &'static str {
+    match color {
+        Some(TurnPlayer::Black) => "black",
+        Some(TurnPlayer::White) => "white",
+        None => "draw",
+    }
+}
+
+// 必要なら引用符で囲む  Quote a CSV field when needed
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+// game_json と同じ列 着手と時間は空白区切り  The game_json columns, lists separated by spaces
+pub fn csv_row(game: &Value) -> String {
+    CSV_HEADER
+        .split(',')
+        .map(|column| {
+            let text = match &game[column] {
+                Value::String(text) => text.clone(),
+                Value::Null => String::new(),
+                Value::Array(items) => items
+                    .iter()
+                    .map(|item| item.as_str().map_or_else(|| item.to_string(), str::to_string))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                value => value.to_string(),
+            };
+            csv_field(&text)
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// 対局ごとに CSV の行と GGF を書き、JSON の配列を書き直す 中断してもそれまでの対局が残る
+// Writes a CSV row and a GGF file and rewrites the JSON array after every game, so an
+// interrupted match keeps the games played so far
+#[derive(Default)]
+pub struct ResultWriter {
+    csv: Option<File>,
+    json: Option<(String, Vec<Value>)>,
+    ggf: Option<String>,
+}
+
+impl ResultWriter {
+    pub fn new(csv: Option<&str>, json: Option<&str>, ggf: Option<&str>) -> Result<Self, String> {
+        let mut writer = ResultWriter::default();
+        if let Some(path) = csv {
+            let mut file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+            writeln!(file, "{}", CSV_HEADER).map_err(|e| format!("{}: {}", path, e))?;
+            writer.csv = Some(file);
+        }
+        if let Some(path) = json {
+            write_json(path, &[])?;
+            writer.json = Some((path.to_string(), Vec::new()));
+        }
+        if let Some(dir) = ggf {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+            writer.ggf = Some(dir.to_string());
+        }
+        Ok(writer)
+    }
+
+    pub fn write(
+        &mut self,
+        number: usize,
+        tournament: &Tournament,
+        result: &GameResult,
+    ) -> Result<(), String> {
+        let game = game_json(number, tournament, result);
+        if let Some(file) = &mut self.csv {
+            writeln!(file, "{}", csv_row(&game)).map_err(|e| e.to_string())?;
+        }
+        if let Some(dir) = &self.ggf {
+            let path = Path::new(dir).join(format!("game-{:04}.ggf", number + 1));
+            fs::write(&path, to_ggf(&result.board) + "\n")
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+        if let Some((path, games)) = &mut self.json {
+            games.push(game);
+            write_json(path, games)?;
+        }
+        Ok(())
+    }
+}
+
+// 一時ファイルに書いてから置き換える  Write a temporary file, then rename it over the old one
+fn write_json(path: &str, games: &[Value]) -> Result<(), String> {
+    let text = serde_json::to_string_pretty(games).map_err(|e| e.to_string())?;
+    let temporary = format!("{}.tmp", path);
+    fs::write(&temporary, text + "\n").map_err(|e| format!("{}: {}", temporary, e))?;
+    fs::rename(&temporary, path).map_err(|e| format!("{}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::opening::parse_suite;
+    use crate::tournament::{make_entrants, Schedule};
+
+    #[test]
+    fn test_game_records() {
+        let specs = ["ab:depth=1", "pm:playouts=4,depth=2"];
+        let tournament = Tournament {
+            entrants: make_entrants(
+                specs.iter().map(|s| (s.to_string(), Engine::parse(s).unwrap())).collect(),
+            ),
+            schedule: Schedule::RoundRobin,
+            games: 1,
+            alternate: true,
+            time_control: None,
+            openings: parse_suite("f5d6").unwrap(),
+            sprt: None,
+            threads: 1,
+            seed: 7,
+        };
+        let result = tournament.play_game(0, 1, 0, 7);
+        let game = game_json(0, &tournament, &result);
+        assert_eq!(game["seed"], 7);
+        assert_eq!(game["opening"], "f5d6");
+        assert_eq!(game["white_settings"], "primitive_montecarlo:playouts=4");
+        // 着手は開始局面の f5d6 から  The moves start with the opening's f5d6
+        let moves = game["moves"].as_array().unwrap();
+        assert_eq!(game["opening_plies"], 2);
+        assert_eq!(moves[..2], [json!("f5"), json!("d6")]);
+        assert_eq!(moves.len(), 2 + result.board.history.len());
+        assert_eq!(game["move_times"].as_array().unwrap().len(), moves.len());
+        // 同じ種なら同じ対局  The same seed plays the same game
+        let again = game_json(0, &tournament, &tournament.play_game(0, 1, 0, 7));
+        assert_eq!(again["moves"], game["moves"]);
+
+        let row = csv_row(&game);
+        assert!(row.starts_with("1,ab:depth=1,\"pm:playouts=4,depth=2\",alphabeta:depth=1,"));
+        assert_eq!(row.matches(',').count(), CSV_HEADER.matches(',').count() + 1);
+        assert_eq!(csv_field("a\"b"), "\"a\"\"b\"");
+
+        // JSON は対局ごとに書き直す  The JSON file is rewritten after every game
+        // 同時に走る cargo test とぶつからない名前  A name no concurrent test run shares
+        let name = format!("othello_test_results_{}.json", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let path = path.to_str().unwrap();
+        let mut writer = ResultWriter::new(None, Some(path), None).unwrap();
+        assert_eq!(fs::read_to_string(path).unwrap(), "[]\n");
+        writer.write(0, &tournament, &result).unwrap();
+        let games: Vec<Value> = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(games, vec![game]);
+        fs::remove_file(path).unwrap();
+    }
+}
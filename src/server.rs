@@ -0,0 +1,209 @@
+// local HTTP/REST server for Othello
+// POST /<cmd> の JSON 本文を JSON lines と同じように処理する  The JSON body of POST /<cmd>
+// is handled like a JSON lines request with that cmd
+
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{sync_channel, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::engine::Limits;
+use crate::jsonl::{handle_value, SOLVE_MAX_EMPTIES};
+
+pub const DEFAULT_PORT: u16 = 8080;
+const MAX_BODY: usize = 64 * 1024;
+const ENDPOINTS: [&str; 5] = ["legal_moves", "apply_move", "move", "analyze", "solve"];
+// 処理するスレッド数と待たせる接続数 あふれた接続には 503 を返す
+// Worker threads and queued connections, connections beyond them get 503
+const WORKERS: usize = 4;
+const QUEUE: usize = 16;
+// 本文を読み終えるまでの時間  Time allowed to send the request
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 1リクエストのエンジンの limits の上限 solve は exact の空きまでしか受け付けない
+// Upper bounds on the engine limits of one request; solve takes at most exact empties
+fn max_limits() -> Limits {
+    Limits {
+        depth: 16,
+        exact: SOLVE_MAX_EMPTIES,
+        playouts: 1_000_000,
+        time: Some(Duration::from_secs(10)),
+    }
+}
+
+// 127.0.0.1 だけで待ち受ける  Listen on the loopback address only
+pub fn run(port: u16) -> Result<(), String> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| format!("{}: {}", port, e))?;
+    let address = listener.local_addr().map_err(|e| e.to_string())?;
+    println!("http://{} で待ち受けています", address);
+    serve(listener);
+    Ok(())
+}
+
+// 決まった数のスレッドで接続を処理する  A fixed pool of threads handles the connections
+fn serve(listener: TcpListener) {
+    let (sender, receiver) = sync_channel::<TcpStream>(QUEUE);
+    let receiver = Arc::new(Mutex::new(receiver));
+    for _ in 0..WORKERS {
+        let receiver = Arc::clone(&receiver);
+        thread::spawn(move || loop {
+            // ロックは受け取ったらすぐ離す  The lock is released as soon as a stream arrives
+            let stream = match receiver.lock() {
+                Ok(receiver) => receiver.recv(),
+                Err(_) => break,
+            };
+            let Ok(stream) = stream else { break };
+            if let Err(e) = handle_connection(stream) {
+                eprintln!("{}", e);
+            }
+        });
+    }
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(TrySendError::Full(stream)) = sender.try_send(stream) {
+                    let busy = json!({"ok": false, "error": "server busy"});
+                    if let Err(e) = respond(stream, "503 Service Unavailable", &busy) {
+                        eprintln!("{}", e);
+                    }
+                }
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
+
+fn handle_connection(stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    let (status, body) = if content_length > MAX_BODY {
+        ("413 Payload Too Large", json!({"ok": false, "error": "body too large"}))
+    } else {
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+        let mut words = request_line.split_whitespace();
+        let (method, target) = (words.next().unwrap_or(""), words.next().unwrap_or(""));
+        route(method, target, &String::from_utf8_lossy(&body))
+    };
+    respond(stream, status, &body)
+}
+
+fn respond(mut stream: TcpStream, status: &str, body: &Value) -> io::Result<()> {
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+// (ステータス, 本文)  (status line, response body)
+fn route(method: &str, target: &str, body: &str) -> (&'static str, Value) {
+    let path = target.split('?').next().unwrap_or("").trim_matches('/');
+    if method == "GET" && path.is_empty() {
+        return ("200 OK", json!({"ok": true, "endpoints": ENDPOINTS}));
+    }
+    if !ENDPOINTS.contains(&path) {
+        return ("404 Not Found", json!({"ok": false, "error": format!("not found: /{}", path)}));
+    }
+    if method != "POST" {
+        return ("405 Method Not Allowed", json!({"ok": false, "error": "use POST"}));
+    }
+    let mut request: Value = match body.trim() {
+        "" => json!({}),
+        body => match serde_json::from_str(body) {
+            Ok(request) => request,
+            Err(e) => return ("400 Bad Request", json!({"ok": false, "error": e.to_string()})),
+        },
+    };
+    let Some(object) = request.as_object_mut() else {
+        return ("400 Bad Request", json!({"ok": false, "error": "body must be an object"}));
+    };
+    object.insert("cmd".to_string(), json!(path));
+    let response = handle_value(request, Some(&max_limits()));
+    match response["ok"].as_bool() {
+        Some(true) => ("200 OK", response),
+        _ => ("400 Bad Request", response),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ローカルのクライアントで1つリクエストを送る  Send one request with a local client
+    fn send(address: &str, request: &str) -> (String, Value) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.lines().next().unwrap().to_string();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    fn post(address: &str, path: &str, body: &str) -> (String, Value) {
+        send(
+            address,
+            &format!(
+                "POST {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+                 Content-Length: {}\r\n\r\n{}",
+                path,
+                body.len(),
+                body
+            ),
+        )
+    }
+
+    #[test]
+    fn test_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || serve(listener));
+
+        let (status, body) = post(&address, "/legal_moves", r#"{"position":"f5"}"#);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body["moves"], json!(["F6", "D6", "F4"]));
+        let (_, body) = post(&address, "/apply_move", r#"{"position":"f5","move":"d6"}"#);
+        assert_eq!(body["to_move"], "black");
+        let (_, body) = post(&address, "/move", r#"{"engine":"alphabeta:depth=2"}"#);
+        assert!(["C4", "D3", "E6", "F5"].contains(&body["move"].as_str().unwrap()));
+        let solve = r#"{"black":"0x6000100810120500","white":"0x8EFCEFF76F6D3A3F"}"#;
+        let (_, body) = post(&address, "/solve", solve);
+        assert_eq!(body["score"], json!({"type": "exact", "value": 10}));
+        assert_eq!(body["solved"], true);
+        let (status, body) = post(&address, "/apply_move", r#"{"move":"a1"}"#);
+        assert_eq!(status, "HTTP/1.1 400 Bad Request");
+        assert_eq!(body["ok"], false);
+        let (status, _) = post(&address, "/shutdown", "");
+        assert_eq!(status, "HTTP/1.1 404 Not Found");
+        let (status, _) = send(&address, "GET /solve HTTP/1.1\r\n\r\n");
+        assert_eq!(status, "HTTP/1.1 405 Method Not Allowed");
+        // 読み切れない局面は時間制限があっても断る  Too many empties are refused even with a time
+        let (status, body) = post(&address, "/solve", r#"{"limits":{"time":0.05}}"#);
+        assert_eq!(status, "HTTP/1.1 400 Bad Request");
+        assert_eq!(body["error"], "solve is limited to 14 empties: 60");
+    }
+}
@@ -0,0 +1,197 @@
+// match statistics: Elo difference, likelihood of superiority and SPRT
+
+use std::fmt;
+
+// 95% の信頼区間の幅 (標準偏差の倍数)  Standard deviations for a 95% interval
+const Z95: f64 = 1.959964;
+
+// 得点率から Elo 差  Elo difference for a score between 0 and 1
+pub fn elo_from_score(score: f64) -> f64 {
+    let score = score.clamp(1e-6, 1.0 - 1e-6);
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+// Elo 差から期待得点率  Expected score for an Elo difference
+pub fn score_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+// 誤差関数 (Abramowitz and Stegun 7.1.26, 誤差 1.5e-7)  Error function approximation
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let y = 1.0 - poly * (-x * x).exp();
+    if x < 0.0 {
+        -y
+    } else {
+        y
+    }
+}
+
+// 勝ち, 負け, 引き分けの数から1局あたりの得点の平均と分散
+// Mean and variance of the per-game score from win, loss and draw counts
+fn score_moments(wins: u32, losses: u32, draws: u32) -> Option<(f64, f64)> {
+    let n = (wins + losses + draws) as f64;
+    if n == 0.0 {
+        return None;
+    }
+    let (w, l, d) = (wins as f64 / n, losses as f64 / n, draws as f64 / n);
+    let mean = w + 0.5 * d;
+    let variance = w * (1.0 - mean).powi(2) + l * mean.powi(2) + d * (0.5 - mean).powi(2);
+    Some((mean, variance))
+}
+
+// Elo 差と 95% の誤差, 優位である確率  Elo difference, its 95% error and the LOS
+#[derive(Clone, Debug, PartialEq)]
+pub struct EloEstimate {
+    pub elo: f64,
+    pub error: f64,
+    pub los: f64, // likelihood of superiority, 0.0 to 1.0
+}
+
+impl EloEstimate {
+    pub fn new(wins: u32, losses: u32, draws: u32) -> Option<Self> {
+        let (mean, variance) = score_moments(wins, losses, draws)?;
+        let n = (wins + losses + draws) as f64;
+        let deviation = (variance / n).sqrt() * Z95;
+        let error = (elo_from_score(mean + deviation) - elo_from_score(mean - deviation)) / 2.0;
+        // 引き分けを除いた勝ち負けの差から  From the decisive games only
+        let los = match wins + losses {
+            0 => 0.5,
+            decisive => {
+                let z = (wins as f64 - losses as f64) / (2.0 * decisive as f64).sqrt();
+                0.5 * (1.0 + erf(z))
+            }
+        };
+        Some(EloEstimate {
+            elo: elo_from_score(mean),
+            error,
+            los,
+        })
+    }
+}
+
+impl fmt::Display for EloEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Elo {:+.1} +/- {:.1}, LOS {:.1}%", self.elo, self.error, self.los * 100.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Decision {
+    Continue,
+    AcceptH0, // elo0 を採る (変更は強くない)  elo0 holds: the change is not stronger
+    AcceptH1, // elo1 を採る (変更は強い)  elo1 holds: the change is stronger
+}
+
+// 逐次確率比検定 H0: Elo = elo0, H1: Elo = elo1
+// Sequential probability ratio test of H0: Elo = elo0 against H1: Elo = elo1
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    // "elo0,elo1" または "elo0,elo1,alpha,beta" (alpha, beta の既定は 0.05)
+    // "elo0,elo1" or "elo0,elo1,alpha,beta", alpha and beta default to 0.05
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let values: Vec<f64> = text
+            .split(',')
+            .map(|s| s.trim().parse().map_err(|_| format!("bad sprt value: {}", s)))
+            .collect::<Result<_, _>>()?;
+        let sprt = match values[..] {
+            [elo0, elo1] => Sprt { elo0, elo1, alpha: 0.05, beta: 0.05 },
+            [elo0, elo1, alpha, beta] => Sprt { elo0, elo1, alpha, beta },
+            _ => return Err(format!("expected elo0,elo1[,alpha,beta]: {}", text)),
+        };
+        // 0 は境界が無限になるので受け付けない  0 would make the bounds infinite
+        let probability = |p: f64| p > 0.0 && p < 0.5;
+        if sprt.elo0 >= sprt.elo1 || !probability(sprt.alpha) || !probability(sprt.beta) {
+            return Err(format!("need elo0 < elo1 and 0 < alpha, beta < 0.5: {}", text));
+        }
+        Ok(sprt)
+    }
+
+    // (下限, 上限)  (lower, upper) bounds of the log likelihood ratio
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    // 正規近似の対数尤度比  Log likelihood ratio under the normal approximation
+    pub fn llr(&self, wins: u32, losses: u32, draws: u32) -> f64 {
+        let Some((mean, variance)) = score_moments(wins, losses, draws) else {
+            return 0.0;
+        };
+        if variance <= 0.0 {
+            return 0.0; // 全勝や全引き分けでは判断しない  No decision from identical results
+        }
+        let n = (wins + losses + draws) as f64;
+        let (s0, s1) = (score_from_elo(self.elo0), score_from_elo(self.elo1));
+        n * (s1 - s0) * (2.0 * mean - s0 - s1) / (2.0 * variance)
+    }
+
+    pub fn decide(&self, wins: u32, losses: u32, draws: u32) -> Decision {
+        let llr = self.llr(wins, losses, draws);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            Decision::AcceptH1
+        } else if llr <= lower {
+            Decision::AcceptH0
+        } else {
+            Decision::Continue
+        }
+    }
+}
+
+impl fmt::Display for Sprt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SPRT elo0={} elo1={} alpha={} beta={}",
+            self.elo0, self.elo1, self.alpha, self.beta
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elo() {
+        assert!((elo_from_score(0.75) - 190.85).abs() < 0.01);
+        assert!((score_from_elo(elo_from_score(0.3)) - 0.3).abs() < 1e-9);
+        let even = EloEstimate::new(10, 10, 5).unwrap();
+        assert!(even.elo.abs() < 1e-9 && (even.los - 0.5).abs() < 1e-9);
+        let strong = EloEstimate::new(30, 10, 10).unwrap();
+        assert!(strong.elo > 100.0 && strong.error > 0.0 && strong.los > 0.99);
+        assert!(EloEstimate::new(0, 0, 0).is_none());
+        assert!((erf(1.0) - 0.842700).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_sprt() {
+        let sprt = Sprt::parse("0,10").unwrap();
+        assert_eq!(sprt.beta, 0.05);
+        let (lower, upper) = sprt.bounds();
+        assert!((upper - 2.944).abs() < 0.001 && (lower + 2.944).abs() < 0.001);
+        assert_eq!(sprt.decide(10, 10, 0), Decision::Continue);
+        assert_eq!(sprt.decide(600, 400, 0), Decision::AcceptH1);
+        assert_eq!(sprt.decide(400, 600, 0), Decision::AcceptH0);
+        assert_eq!(sprt.decide(5, 0, 0), Decision::Continue);
+        assert!(Sprt::parse("10,0").is_err());
+        assert!(Sprt::parse("0,10,0.05").is_err());
+        assert!(Sprt::parse("0,10,0,0.05").is_err());
+        assert!(Sprt::parse("0,10,0.05,0.0").is_err());
+        assert!(Sprt::parse("0,10,0.05,0.5").is_err());
+        assert!(Sprt::parse("0,10,0.01,0.1").is_ok());
+    }
+}
@@ -0,0 +1,586 @@
+// engine tournaments for Othello: round robin or gauntlet with a crosstable
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// from  bit_lib
+use crate::bit_lib::can_locate;
+use crate::board::Board;
+use crate::clock::TimeControl;
+use crate::engine::{legal_move, Engine};
+use crate::monte_carlo::seed_random;
+use crate::opening::{generate_suite, load_suite, Opening, DEFAULT_DEPTH, DEFAULT_MARGIN};
+use crate::position::Position;
+use crate::results::ResultWriter;
+use crate::stats::{Decision, EloEstimate, Sprt};
+use crate::{Pmove, TurnPlayer}; // from lib
+
+// 1組あたりの既定の対局数  Default number of games per pairing
+pub const DEFAULT_GAMES: usize = 2;
+
+pub struct Entrant {
+    pub name: String,
+    pub engine: Engine,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Schedule {
+    RoundRobin, // 全員が総当たり  Everyone plays everyone
+    Gauntlet,   // 最初のエンジンが他の全員と対戦  The first engine plays all the others
+}
+
+pub struct Tournament {
+    pub entrants: Vec<Entrant>,
+    pub schedule: Schedule,
+    pub games: usize,     // 1組, 1開始局面あたりの対局数  Games per pairing and opening
+    pub alternate: bool,  // 1局ごとに先後を入れ替える  Swap colours every other game
+    pub time_control: Option<TimeControl>,
+    pub openings: Vec<Opening>,
+    pub sprt: Option<Sprt>, // 2つのエンジンの対戦を途中で打ち切る  Stops a two-engine match early
+    pub threads: usize,     // 同時に進める対局の数  Games played at the same time
+    pub seed: u64,          // n 局目の乱数の種は seed + n  Game n is seeded with seed + n
+}
+
+// 1局の結果 board は終局図と全ての着手を持つ
+// The result of one game, board holds the final position and every move
+pub struct GameResult {
+    pub black: usize, // 参加者の番号  Entrant index
+    pub white: usize,
+    pub opening: usize, // 開始局面の番号  Opening index
+    pub seed: u64,
+    pub board: Board,
+    pub flagged: Option<TurnPlayer>, // 時間切れ負けの側  The side that lost on time
+}
+
+impl GameResult {
+    // 黒から見た石差  Disc difference from Black's view
+    pub fn disc_diff(&self) -> i32 {
+        self.board.black.count_ones() as i32 - self.board.white.count_ones() as i32
+    }
+
+    // 勝った側 引き分けは None  The winning side, None for a draw
+    pub fn winner(&self) -> Option<TurnPlayer> {
+        if let Some(loser) = &self.flagged {
+            return Some(loser.flip());
+        }
+        match self.disc_diff() {
+            diff if diff > 0 => Some(TurnPlayer::Black),
+            diff if diff < 0 => Some(TurnPlayer::White),
+            _ => None,
+        }
+    }
+
+    pub fn time_used(&self, color: &TurnPlayer) -> Duration {
+        self.board
+            .history
+            .iter()
+            .filter(|record| record.turn_player == *color)
+            .map(|record| record.time)
+            .sum()
+    }
+}
+
+// 1人分の成績  One entrant's totals
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Standing {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    pub discs: i32,
+    pub time: Duration,
+    pub moves: u32,
+}
+
+impl Standing {
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    // 勝ちを1、引き分けを0.5とした得点率  Score with a draw counted as half a win
+    pub fn score(&self) -> f64 {
+        match self.games() {
+            0 => 0.0,
+            games => (self.wins as f64 + 0.5 * self.draws as f64) / games as f64,
+        }
+    }
+}
+
+// 参加者ごとの成績と対戦成績の表  Totals per entrant and head-to-head results
+pub struct Crosstable {
+    pub names: Vec<String>,
+    pub standings: Vec<Standing>,
+    pub pairs: Vec<Vec<Standing>>, // pairs[i][j]: i から見た j との対戦成績  i's results against j
+    pub sprt: Option<(Sprt, Decision)>,
+}
+
+impl Crosstable {
+    pub fn new(names: Vec<String>) -> Self {
+        let n = names.len();
+        Crosstable {
+            names,
+            standings: vec![Standing::default(); n],
+            pairs: vec![vec![Standing::default(); n]; n],
+            sprt: None,
+        }
+    }
+
+    // i から見た j との Elo 差  Elo of i against j
+    pub fn elo(&self, i: usize, j: usize) -> Option<EloEstimate> {
+        let pair = &self.pairs[i][j];
+        EloEstimate::new(pair.wins, pair.losses, pair.draws)
+    }
+
+    pub fn add(&mut self, result: &GameResult) {
+        let winner = result.winner();
+        let sides = [
+            (result.black, result.white, TurnPlayer::Black, result.disc_diff()),
+            (result.white, result.black, TurnPlayer::White, -result.disc_diff()),
+        ];
+        for (me, opponent, color, discs) in sides {
+            let moves = result
+                .board
+                .history
+                .iter()
+                .filter(|record| record.turn_player == color && record.pmove != Pmove::Passmove)
+                .count() as u32;
+            let time = result.time_used(&color);
+            for standing in [&mut self.standings[me], &mut self.pairs[me][opponent]] {
+                match &winner {
+                    Some(side) if *side == color => standing.wins += 1,
+                    Some(_) => standing.losses += 1,
+                    None => standing.draws += 1,
+                }
+                standing.discs += discs;
+                standing.time += time;
+                standing.moves += moves;
+            }
+        }
+    }
+}
+
+impl fmt::Display for Crosstable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.names.iter().map(|name| name.len()).max().unwrap_or(0).max(6);
+        writeln!(
+            f,
+            " #  {:width$}  games   win  loss  draw   score   discs     time  per move",
+            "engine"
+        )?;
+        for (i, (name, standing)) in self.names.iter().zip(&self.standings).enumerate() {
+            let per_move = match standing.moves {
+                0 => 0.0,
+                moves => standing.time.as_secs_f64() / moves as f64,
+            };
+            writeln!(
+                f,
+                "{:2}  {:width$}  {:5} {:5} {:5} {:5}  {:5.1}%  {:+6}  {:6.1}s  {:7.3}s",
+                i + 1,
+                name,
+                standing.games(),
+                standing.wins,
+                standing.losses,
+                standing.draws,
+                standing.score() * 100.0,
+                standing.discs,
+                standing.time.as_secs_f64(),
+                per_move
+            )?;
+        }
+        // 行の参加者から見た 勝-敗-分  Win-loss-draw from the row entrant's view
+        writeln!(f)?;
+        write!(f, "    {:width$}", "")?;
+        for j in 0..self.names.len() {
+            write!(f, " {:>9}", j + 1)?;
+        }
+        writeln!(f)?;
+        for (i, name) in self.names.iter().enumerate() {
+            write!(f, "{:2}  {:width$}", i + 1, name)?;
+            for (j, pair) in self.pairs[i].iter().enumerate() {
+                let cell = if i == j || pair.games() == 0 {
+                    "-".to_string()
+                } else {
+                    format!("{}-{}-{}", pair.wins, pair.losses, pair.draws)
+                };
+                write!(f, " {:>9}", cell)?;
+            }
+            writeln!(f)?;
+        }
+        // 対戦ごとの Elo 差  Elo difference of every pairing
+        writeln!(f)?;
+        for i in 0..self.names.len() {
+            for j in i + 1..self.names.len() {
+                if let Some(elo) = self.elo(i, j) {
+                    writeln!(f, "{} vs {}: {}", self.names[i], self.names[j], elo)?;
+                }
+            }
+        }
+        if let Some((sprt, decision)) = &self.sprt {
+            let pair = &self.pairs[0][1];
+            let llr = sprt.llr(pair.wins, pair.losses, pair.draws);
+            let (lower, upper) = sprt.bounds();
+            let result = match decision {
+                Decision::AcceptH0 => "H0 accepted",
+                Decision::AcceptH1 => "H1 accepted",
+                Decision::Continue => "inconclusive",
+            };
+            writeln!(f, "{}: {} (LLR {:.2}, bounds {:.2} {:.2})", sprt, result, llr, lower, upper)?;
+        }
+        Ok(())
+    }
+}
+
+impl Tournament {
+    // 対局ごとの (黒, 白, 開始局面) を対局順に  (black, white, opening) of every game in order
+    pub fn schedule_games(&self) -> Vec<(usize, usize, usize)> {
+        let n = self.entrants.len();
+        let pairs: Vec<(usize, usize)> = match self.schedule {
+            Schedule::RoundRobin => (0..n).flat_map(|i| (i + 1..n).map(move |j| (i, j))).collect(),
+            Schedule::Gauntlet => (1..n).map(|j| (0, j)).collect(),
+        };
+        let mut games = Vec::new();
+        for (i, j) in pairs {
+            for opening in 0..self.openings.len() {
+                for game in 0..self.games {
+                    let swap = self.alternate && game % 2 == 1;
+                    games.push(if swap { (j, i, opening) } else { (i, j, opening) });
+                }
+            }
+        }
+        games
+    }
+
+    // 全ての対局を行い 1局ごとに on_game を呼ぶ SPRT が決まれば残りの対局はしない
+    // 対局は threads 個のスレッドで並べて進めるが、結果は対局順に集計するので
+    // 表はスレッドの数や終わった順によらない
+    // Play every game, calling on_game after each, until the SPRT reaches a decision.
+    // Games run on threads workers, but results are tallied in game order, so the report
+    // does not depend on the number of threads or the order in which games finish
+    pub fn run(&self, mut on_game: impl FnMut(usize, &GameResult, &Crosstable)) -> Crosstable {
+        let mut crosstable = Crosstable::new(self.entrants.iter().map(|e| e.name.clone()).collect());
+        if let Some(sprt) = &self.sprt {
+            crosstable.sprt = Some((sprt.clone(), Decision::Continue));
+        }
+        let games = self.schedule_games();
+        let next = AtomicUsize::new(0);
+        let stop = AtomicBool::new(false);
+        thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+            for _ in 0..self.threads.clamp(1, games.len().max(1)) {
+                let sender = sender.clone();
+                let (games, next, stop) = (&games, &next, &stop);
+                scope.spawn(move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        let number = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(&(black, white, opening)) = games.get(number) else {
+                            break;
+                        };
+                        let seed = self.seed.wrapping_add(number as u64);
+                        let result = self.play_game(black, white, opening, seed);
+                        if sender.send((number, result)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+            // 先に終わった対局は前の対局が揃うまで待たせる  Hold results until earlier games finish
+            let mut pending = BTreeMap::new();
+            let mut tallied = 0;
+            for (number, result) in receiver {
+                pending.insert(number, result);
+                while let Some(result) = pending.remove(&tallied) {
+                    crosstable.add(&result);
+                    on_game(tallied, &result, &crosstable);
+                    tallied += 1;
+                    if self.decide(&mut crosstable) {
+                        // 決まった後の対局は数えない  Games after the decision are not counted
+                        stop.store(true, Ordering::Relaxed);
+                        return;
+                    }
+                }
+            }
+        });
+        crosstable
+    }
+
+    // SPRT が決まったか  Whether the SPRT has reached a decision
+    fn decide(&self, crosstable: &mut Crosstable) -> bool {
+        let Some((sprt, decision)) = &mut crosstable.sprt else {
+            return false;
+        };
+        let pair = &crosstable.pairs[0][1];
+        *decision = sprt.decide(pair.wins, pair.losses, pair.draws);
+        *decision != Decision::Continue
+    }
+
+    pub fn play_game(&self, black: usize, white: usize, opening: usize, seed: u64) -> GameResult {
+        seed_random(seed);
+        let start = &self.openings[opening].position;
+        let (black_engine, white_engine) = (&self.entrants[black].engine, &self.entrants[white].engine);
+        let mut board = Board::new(
+            (
+                start.turn_player.clone(),
+                self.entrants[black].name.clone(),
+                self.entrants[white].name.clone(),
+            ),
+            start.black,
+            start.white,
+        );
+        while board.is_can_play() {
+            let (first, second, left) = (board.first, board.second, board.left);
+            if can_locate(first, second) == 0 {
+                board.update_board_timed(Pmove::Passmove, Duration::ZERO);
+                continue;
+            }
+            let engine = match board.turn_player {
+                TurnPlayer::Black => black_engine,
+                TurnPlayer::White => white_engine,
+            };
+            let start = Instant::now();
+            let pmove = match &self.time_control {
+                Some(time_control) => {
+                    engine.think_for(first, second, left, time_control.budget(&board))
+                }
+                None => engine.think(first, second, left),
+            };
+            // 合法でない手はパスとして打たず最初の合法手にする  A move that is not legal is
+            // replaced with the first legal move rather than played as a pass
+            let pmove = legal_move(pmove, first, second);
+            let used = start.elapsed();
+            if let Some(time_control) = &self.time_control {
+                if time_control.is_flagged(&board, used) {
+                    let flagged = Some(board.turn_player.clone());
+                    return GameResult { black, white, opening, seed, board, flagged };
+                }
+            }
+            board.update_board_timed(pmove, used);
+        }
+        GameResult { black, white, opening, seed, board, flagged: None }
+    }
+}
+
+// 同じ名前には #2 などを付ける  Number duplicate names as #2, #3...
+pub fn make_entrants(engines: Vec<(String, Engine)>) -> Vec<Entrant> {
+    let mut entrants: Vec<Entrant> = Vec::new();
+    for (name, engine) in engines {
+        let count = entrants.iter().filter(|e| e.name.split(" #").next() == Some(name.as_str())).count();
+        let name = if count == 0 { name } else { format!("{} #{}", name, count + 1) };
+        entrants.push(Entrant { name, engine });
+    }
+    entrants
+}
+
+// 開始局面1つだけの組  A suite with the single start position
+pub fn single_opening(start: Position) -> Vec<Opening> {
+    let name = if start == Position::default() { "start".to_string() } else { start.to_hex() };
+    vec![Opening::new(&name, start)]
+}
+
+// "6" なら互角の6手の局面を作り、それ以外はファイルから読む
+// A number generates balanced openings of that many plies, anything else is a suite file
+pub fn openings_option(value: &str) -> Result<Vec<Opening>, String> {
+    match value.parse::<usize>() {
+        Ok(plies) => {
+            let mut judge = Engine::parse("alphabeta")?;
+            judge.limits.depth = DEFAULT_DEPTH;
+            let suite: Vec<Opening> = generate_suite(plies, &judge, DEFAULT_MARGIN)
+                .into_iter()
+                .map(|(opening, _)| opening)
+                .collect();
+            if suite.is_empty() {
+                return Err(format!("no balanced {}-ply openings", plies));
+            }
+            Ok(suite)
+        }
+        Err(_) => load_suite(value),
+    }
+}
+
+// Othello match <engine> <engine>... [--games N] [--gauntlet] [--no-alternate] [--openings F]
+pub fn run_command(args: &[String], start: Position, time_control: Option<TimeControl>) -> Result<(), String> {
+    let usage = "usage: match <engine> <engine>... [--games N] [--gauntlet] [--no-alternate] \
+                 [--openings <file|plies>] [--sprt elo0,elo1[,alpha,beta]] [--threads N] [--seed N] \
+                 [--csv <file>] [--json <file>] [--ggf <dir>]";
+    let mut specs = Vec::new();
+    let (mut csv, mut json, mut ggf) = (None, None, None);
+    let mut tournament = Tournament {
+        entrants: Vec::new(),
+        schedule: Schedule::RoundRobin,
+        games: DEFAULT_GAMES,
+        alternate: true,
+        time_control,
+        openings: single_opening(start),
+        sprt: None,
+        threads: 1,
+        seed: rand::random(),
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--games" => {
+                let games = args.next().ok_or(usage)?;
+                tournament.games = games.parse().map_err(|_| format!("bad games: {}", games))?;
+            }
+            "--gauntlet" => tournament.schedule = Schedule::Gauntlet,
+            "--no-alternate" => tournament.alternate = false,
+            "--openings" => tournament.openings = openings_option(args.next().ok_or(usage)?)?,
+            "--sprt" => tournament.sprt = Some(Sprt::parse(args.next().ok_or(usage)?)?),
+            "--csv" => csv = Some(args.next().ok_or(usage)?),
+            "--json" => json = Some(args.next().ok_or(usage)?),
+            "--ggf" => ggf = Some(args.next().ok_or(usage)?),
+            "--seed" => {
+                let seed = args.next().ok_or(usage)?;
+                tournament.seed = seed.parse().map_err(|_| format!("bad seed: {}", seed))?;
+            }
+            "--threads" => {
+                let threads = args.next().ok_or(usage)?;
+                tournament.threads = match threads.parse() {
+                    Ok(0) => thread::available_parallelism().map_or(1, |n| n.get()),
+                    Ok(threads) => threads,
+                    Err(_) => return Err(format!("bad threads: {}", threads)),
+                };
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option: {}\n{}", arg, usage)),
+            _ => specs.push((arg.clone(), Engine::parse(arg)?)),
+        }
+    }
+    if specs.len() < 2 {
+        return Err(usage.to_string());
+    }
+    if tournament.sprt.is_some() && specs.len() != 2 {
+        return Err("--sprt needs exactly two engines".to_string());
+    }
+    tournament.entrants = make_entrants(specs);
+    let total = tournament.schedule_games().len();
+    let mut writer = ResultWriter::new(
+        csv.map(String::as_str),
+        json.map(String::as_str),
+        ggf.map(String::as_str),
+    )?;
+    let mut error = Ok(());
+    let crosstable = tournament.run(|number, result, crosstable| {
+        print_game(number, total, &tournament, result, crosstable);
+        if error.is_ok() {
+            error = writer.write(number, &tournament, result);
+        }
+    });
+    print!("\n{}", crosstable);
+    println!("seed {}", tournament.seed);
+    error
+}
+
+// "game 3/20: alphabeta 36-28 MCTS" のような1局の結果 2つのエンジンの対戦では途中の成績も
+// One line per finished game, with the running totals in a two-engine match
+pub fn print_game(
+    number: usize,
+    total: usize,
+    tournament: &Tournament,
+    result: &GameResult,
+    crosstable: &Crosstable,
+) {
+    let flag = match &result.flagged {
+        Some(color) => format!(" ({} lost on time)", color),
+        None => String::new(),
+    };
+    let opening = match tournament.openings.len() {
+        1 => String::new(),
+        _ => format!(" [{}]", tournament.openings[result.opening].name),
+    };
+    println!(
+        "game {}/{}: {} {}-{} {}{}{}",
+        number + 1,
+        total,
+        tournament.entrants[result.black].name,
+        result.board.black.count_ones(),
+        result.board.white.count_ones(),
+        tournament.entrants[result.white].name,
+        flag,
+        opening
+    );
+    if tournament.entrants.len() != 2 {
+        return;
+    }
+    let pair = &crosstable.pairs[0][1];
+    let mut line = format!("  {}-{}-{}", pair.wins, pair.losses, pair.draws);
+    if let Some(elo) = crosstable.elo(0, 1) {
+        line.push_str(&format!("  {}", elo));
+    }
+    if let Some(sprt) = &tournament.sprt {
+        line.push_str(&format!("  LLR {:.2}", sprt.llr(pair.wins, pair.losses, pair.draws)));
+    }
+    println!("{}", line);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opening::parse_suite;
+
+    fn new_tournament(specs: &[&str], schedule: Schedule) -> Tournament {
+        let engines = specs.iter().map(|s| (s.to_string(), Engine::parse(s).unwrap())).collect();
+        Tournament {
+            entrants: make_entrants(engines),
+            schedule,
+            games: 2,
+            alternate: true,
+            time_control: None,
+            openings: single_opening(Position::default()),
+            sprt: None,
+            threads: 1,
+            seed: 1,
+        }
+    }
+
+    #[test]
+    fn test_schedule() {
+        let specs = ["ab:depth=1", "ab:depth=1", "pm:playouts=5"];
+        let round_robin = new_tournament(&specs, Schedule::RoundRobin);
+        assert_eq!(round_robin.entrants[1].name, "ab:depth=1 #2");
+        assert_eq!(
+            round_robin.schedule_games(),
+            vec![(0, 1, 0), (1, 0, 0), (0, 2, 0), (2, 0, 0), (1, 2, 0), (2, 1, 0)]
+        );
+        let mut gauntlet = new_tournament(&specs, Schedule::Gauntlet);
+        assert_eq!(gauntlet.schedule_games(), vec![(0, 1, 0), (1, 0, 0), (0, 2, 0), (2, 0, 0)]);
+        // 開始局面ごとに先後を入れ替えて2局  Each opening twice with colours swapped
+        gauntlet.openings = parse_suite("f5d6\nf5f6\n").unwrap();
+        let games = gauntlet.schedule_games();
+        assert_eq!(games.len(), 8);
+        assert_eq!(&games[..4], &[(0, 1, 0), (1, 0, 0), (0, 1, 1), (1, 0, 1)]);
+    }
+
+    #[test]
+    fn test_crosstable() {
+        let tournament = new_tournament(&["ab:depth=1", "ab:depth=2"], Schedule::RoundRobin);
+        let crosstable = tournament.run(|_, result, _| assert!(!result.board.is_can_play()));
+        let (first, second) = (&crosstable.standings[0], &crosstable.standings[1]);
+        assert_eq!((first.games(), second.games()), (2, 2));
+        assert_eq!(first.wins, second.losses);
+        assert_eq!(first.discs, -second.discs);
+        assert_eq!(crosstable.pairs[0][1], *first);
+        let text = crosstable.to_string();
+        assert!(text.contains(&format!("{}-{}-{}", first.wins, first.losses, first.draws)));
+    }
+
+    #[test]
+    fn test_parallel_games() {
+        // 並べて進めても結果は対局順に届く  Parallel games are reported in game order
+        let mut tournament = new_tournament(&["ab:depth=1", "ab:depth=2"], Schedule::RoundRobin);
+        tournament.openings = parse_suite("f5d6\nf5f6\nf5f4\n").unwrap();
+        let mut serial = Vec::new();
+        let serial_table = tournament.run(|number, result, _| serial.push((number, result.disc_diff())));
+        tournament.threads = 4;
+        let mut parallel = Vec::new();
+        let parallel_table =
+            tournament.run(|number, result, _| parallel.push((number, result.disc_diff())));
+        assert_eq!(serial.len(), 6);
+        assert_eq!(serial, parallel);
+        for (a, b) in serial_table.standings.iter().zip(&parallel_table.standings) {
+            assert_eq!((a.wins, a.losses, a.draws, a.discs), (b.wins, b.losses, b.draws, b.discs));
+        }
+    }
+}
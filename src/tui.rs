@@ -0,0 +1,574 @@
+// full-screen terminal UI for Othello
+
+use std::io;
+use std::io::Write;
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent};
+use crossterm::event::{KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{event, execute, queue, terminal};
+
+// from  bit_lib
+use crate::bit_lib::{can_locate, make_index};
+use crate::board::Board;
+use crate::clock::{format_clock, TimeControl};
+use crate::engine::{legal_move, Engine};
+use crate::position::Position;
+use crate::{Pmove, TurnPlayer}; // from lib
+
+const BOARD_X: u16 = 3; // A列の左端  Left edge of column A
+const BOARD_Y: u16 = 2; // 1行目  Row 1
+const CELL_WIDTH: u16 = 3;
+const PANEL_X: u16 = 30;
+const MOVE_LIST_ROWS: usize = 8;
+const FRAME: Duration = Duration::from_millis(100);
+
+pub enum Player {
+    Human,
+    Engine(Engine),
+}
+
+impl Player {
+    // "human" またはエンジンの指定  "human" or an engine spec such as "alphabeta:depth=6"
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        match spec {
+            "human" | "h" => Ok(Player::Human),
+            _ => Engine::parse(spec).map(Player::Engine),
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Player::Human => "human".to_string(),
+            Player::Engine(engine) => engine.to_string(),
+        }
+    }
+}
+
+// エンジンの思考結果  The result of one engine search
+struct Thought {
+    pmove: Pmove,
+    text: String,
+    time: Duration,
+}
+
+struct Tui {
+    board: Board,
+    players: [Player; 2], // black, white
+    cursor: (u16, u16),   // (列, 行)  (column, row)
+    thinking: Option<Receiver<Thought>>,
+    turn_start: Instant,
+    thought: String,
+    message: String,
+    // エンジンの手に評価と読み筋を添えるか  Whether engine moves come with scores and a PV
+    show_analysis: bool,
+    time_control: Option<TimeControl>,
+    flagged: Option<TurnPlayer>, // 時間切れ負けの側  The side that lost on time
+}
+
+// 終了時やパニック時に端末を元に戻す  Restore the terminal on exit and on panic
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture, Hide)?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), Show, DisableMouseCapture, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+pub fn run(
+    black: Player,
+    white: Player,
+    start: Position,
+    time_control: Option<TimeControl>,
+) -> Result<(), String> {
+    let mut tui = Tui::new(black, white, start, time_control);
+    let _guard = TerminalGuard::new().map_err(|e| e.to_string())?;
+    tui.event_loop(&mut io::stdout()).map_err(|e| e.to_string())
+}
+
+impl Tui {
+    fn new(black: Player, white: Player, start: Position, time_control: Option<TimeControl>) -> Self {
+        Tui {
+            board: Board::new(
+                (start.turn_player, black.name(), white.name()),
+                start.black,
+                start.white,
+            ),
+            players: [black, white],
+            cursor: (5, 4), // F5
+            thinking: None,
+            turn_start: Instant::now(),
+            thought: String::new(),
+            message: String::new(),
+            show_analysis: false,
+            time_control,
+            flagged: None,
+        }
+    }
+
+    // 終局か時間切れ  The game is over or a side lost on time
+    fn is_over(&self) -> bool {
+        self.flagged.is_some() || !self.board.is_can_play()
+    }
+
+    // 手番側の時間切れ 考慮中のエンジンの手は捨てる  The side to move lost on time; a
+    // pending engine move is dropped
+    fn flag(&mut self) {
+        let loser = self.board.turn_player.clone();
+        self.message = format!(
+            "{} の時間切れです {}の勝ちです",
+            color_name(&loser),
+            color_name(&loser.flip())
+        );
+        self.flagged = Some(loser);
+        self.thinking = None;
+    }
+
+    fn event_loop(&mut self, out: &mut impl Write) -> io::Result<()> {
+        execute!(out, Clear(ClearType::All))?;
+        loop {
+            self.step();
+            self.draw(out)?;
+            if !event::poll(FRAME)? {
+                continue;
+            }
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press && !self.on_key(key) => {
+                    return Ok(());
+                }
+                Event::Mouse(mouse) => self.on_mouse(mouse),
+                Event::Resize(_, _) => execute!(out, Clear(ClearType::All))?,
+                _ => {}
+            }
+        }
+    }
+
+    fn player_index(&self) -> usize {
+        match self.board.turn_player {
+            TurnPlayer::Black => 0,
+            TurnPlayer::White => 1,
+        }
+    }
+
+    // パスとエンジンの手番を進める  Advance passes and engine turns
+    fn step(&mut self) {
+        if self.is_over() {
+            return;
+        }
+        if let Some(time_control) = &self.time_control {
+            if time_control.is_flagged(&self.board, self.turn_start.elapsed()) {
+                self.flag();
+                return;
+            }
+        }
+        if let Some(receiver) = &self.thinking {
+            match receiver.try_recv() {
+                Ok(thought) => {
+                    self.thinking = None;
+                    self.thought = thought.text;
+                    self.apply(thought.pmove, thought.time);
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => {
+                    self.thinking = None;
+                    self.message = "エンジンが停止しました".to_string();
+                }
+            }
+            return;
+        }
+        if can_locate(self.board.first, self.board.second) == 0 {
+            self.message = format!("{} はパスです", color_name(&self.board.turn_player));
+            self.apply(Pmove::Passmove, Duration::ZERO);
+            return;
+        }
+        if let Player::Engine(engine) = &self.players[self.player_index()] {
+            let mut engine = engine.clone();
+            // 時計があれば残り時間から考慮時間を決める  Allot the thinking time from the clock
+            if let Some(time_control) = &self.time_control {
+                engine.limits.time = Some(time_control.budget(&self.board));
+            }
+            let (first, second, left) = (self.board.first, self.board.second, self.board.left);
+            let show_analysis = self.show_analysis;
+            let (sender, receiver) = mpsc::channel();
+            thread::spawn(move || {
+                let start = Instant::now();
+                let (pmove, text) = think(&engine, first, second, left, show_analysis);
+                let _ = sender.send(Thought {
+                    pmove,
+                    text,
+                    time: start.elapsed(),
+                });
+            });
+            self.thinking = Some(receiver);
+        }
+    }
+
+    fn apply(&mut self, pmove: Pmove, time: Duration) {
+        if let Some(time_control) = &self.time_control {
+            if time_control.is_flagged(&self.board, time) {
+                self.flag();
+                return;
+            }
+        }
+        self.board.update_board_timed(pmove, time);
+        self.turn_start = Instant::now();
+        if !self.board.is_can_play() {
+            self.message = result_text(&self.board);
+        }
+    }
+
+    fn on_key(&mut self, key: KeyEvent) -> bool {
+        let (column, row) = self.cursor;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Left | KeyCode::Char('h') => self.cursor.0 = column.saturating_sub(1),
+            KeyCode::Right | KeyCode::Char('l') => self.cursor.0 = (column + 1).min(7),
+            KeyCode::Up | KeyCode::Char('k') => self.cursor.1 = row.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.cursor.1 = (row + 1).min(7),
+            KeyCode::Enter | KeyCode::Char(' ') => self.human_move(square_bit(column, row)),
+            KeyCode::Char('u') => self.undo(),
+            KeyCode::Char('e') => {
+                self.show_analysis = !self.show_analysis;
+                self.message = if self.show_analysis {
+                    "次のエンジンの手から評価と読み筋を表示します".to_string()
+                } else {
+                    "評価と読み筋を表示しません".to_string()
+                };
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn on_mouse(&mut self, mouse: MouseEvent) {
+        if mouse.kind != MouseEventKind::Down(MouseButton::Left) {
+            return;
+        }
+        if let Some((column, row)) = square_at(mouse.column, mouse.row) {
+            self.cursor = (column, row);
+            self.human_move(square_bit(column, row));
+        }
+    }
+
+    fn human_move(&mut self, mv: u64) {
+        if self.is_over() || self.thinking.is_some() {
+            return;
+        }
+        if let Player::Engine(_) = self.players[self.player_index()] {
+            return;
+        }
+        if can_locate(self.board.first, self.board.second) & mv == 0 {
+            self.message = format!("{} には打てません", make_index(mv));
+            return;
+        }
+        self.message.clear();
+        self.apply(Pmove::Pvmove(mv), self.turn_start.elapsed());
+    }
+
+    // 人間が打てる手番まで戻す  Take back moves until a human can move
+    fn undo(&mut self) {
+        if self.thinking.is_some() {
+            self.message = "思考中は戻せません".to_string();
+            return;
+        }
+        if self.board.undo().is_none() {
+            self.message = "戻せる手がありません".to_string();
+            return;
+        }
+        self.flagged = None;
+        while (matches!(self.players[self.player_index()], Player::Engine(_))
+            || can_locate(self.board.first, self.board.second) == 0)
+            && self.board.undo().is_some()
+        {}
+        self.turn_start = Instant::now();
+        self.message = "一手戻しました".to_string();
+    }
+
+    fn draw(&self, out: &mut impl Write) -> io::Result<()> {
+        queue!(out, MoveTo(0, 0), Print("Othello_Rust"))?;
+        for column in 0..8u16 {
+            let label = (b'A' + column as u8) as char;
+            queue!(out, MoveTo(BOARD_X + column * CELL_WIDTH + 1, BOARD_Y - 1), Print(label))?;
+        }
+        let legal = can_locate(self.board.first, self.board.second);
+        let last = match self.board.history.last() {
+            Some(record) => match record.pmove {
+                Pmove::Pvmove(mv) => mv,
+                _ => 0,
+            },
+            None => 0,
+        };
+        let flipped = flipped_discs(&self.board);
+        for row in 0..8u16 {
+            queue!(out, MoveTo(0, BOARD_Y + row), Print(row + 1))?;
+            for column in 0..8u16 {
+                let bit = square_bit(column, row);
+                let background = if self.cursor == (column, row) {
+                    Color::Blue
+                } else if bit == last {
+                    Color::DarkYellow
+                } else if bit & flipped != 0 {
+                    Color::DarkCyan
+                } else {
+                    Color::DarkGreen
+                };
+                let (foreground, text) = if self.board.black & bit != 0 {
+                    (Color::Black, " ● ")
+                } else if self.board.white & bit != 0 {
+                    (Color::White, " ● ")
+                } else if legal & bit != 0 {
+                    (Color::Yellow, " · ")
+                } else {
+                    (Color::Black, "   ")
+                };
+                queue!(
+                    out,
+                    MoveTo(BOARD_X + column * CELL_WIDTH, BOARD_Y + row),
+                    SetBackgroundColor(background),
+                    SetForegroundColor(foreground),
+                    Print(text),
+                    ResetColor
+                )?;
+            }
+        }
+        for (y, line) in self.panel_lines().iter().enumerate() {
+            queue!(
+                out,
+                MoveTo(PANEL_X, BOARD_Y + y as u16),
+                Print(line),
+                Clear(ClearType::UntilNewLine)
+            )?;
+        }
+        let bottom = BOARD_Y + 9;
+        queue!(out, MoveTo(0, bottom), Print(&self.message), Clear(ClearType::UntilNewLine))?;
+        queue!(
+            out,
+            MoveTo(0, bottom + 1),
+            Print("矢印/hjkl: 移動  Enter/クリック: 着手  u: 戻す  e: 評価  q: 終了"),
+            Clear(ClearType::UntilNewLine)
+        )?;
+        out.flush()
+    }
+
+    // 対局者, 石数, 時計, 思考, 棋譜  Players, discs, clocks, engine output and moves
+    fn panel_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (index, color) in [TurnPlayer::Black, TurnPlayer::White].iter().enumerate() {
+            let discs = match color {
+                TurnPlayer::Black => self.board.black.count_ones(),
+                TurnPlayer::White => self.board.white.count_ones(),
+            };
+            let marker = if *color == self.board.turn_player && !self.is_over() {
+                '>'
+            } else {
+                ' '
+            };
+            lines.push(format!(
+                "{} {} {:<18} {:2}  {}",
+                marker,
+                color_name(color),
+                truncate(&self.players[index].name(), 18),
+                discs,
+                format_clock(self.clock(color))
+            ));
+        }
+        lines.push(String::new());
+        lines.push(match &self.thinking {
+            Some(_) => format!("思考中 {:.1}s", self.turn_start.elapsed().as_secs_f64()),
+            None => format!("思考: {}", truncate(&self.thought, 40)),
+        });
+        lines.push(String::new());
+        lines.push("棋譜:".to_string());
+        let skip = self.board.history.len().saturating_sub(MOVE_LIST_ROWS);
+        for (number, record) in self.board.history.iter().enumerate().skip(skip) {
+            let mv = match record.pmove {
+                Pmove::Pvmove(mv) => make_index(mv),
+                _ => "PA".to_string(),
+            };
+            lines.push(format!(
+                "{:3}. {} {} {:6.1}s",
+                number + 1,
+                color_name(&record.turn_player),
+                mv,
+                record.time.as_secs_f64()
+            ));
+        }
+        while lines.len() < 5 + MOVE_LIST_ROWS {
+            lines.push(String::new());
+        }
+        lines
+    }
+
+    // 時計があれば残り時間, なければ使った時間 手番側は考慮中の時間も含む
+    // The time left with a time control, otherwise the time used, counting the current turn
+    fn clock(&self, color: &TurnPlayer) -> Duration {
+        let current = if *color == self.board.turn_player && !self.is_over() {
+            self.turn_start.elapsed()
+        } else {
+            Duration::ZERO
+        };
+        if let Some(time_control) = &self.time_control {
+            return time_control.remaining(&self.board, color).saturating_sub(current);
+        }
+        let used: Duration = self
+            .board
+            .history
+            .iter()
+            .filter(|record| record.turn_player == *color)
+            .map(|record| record.time)
+            .sum();
+        used + current
+    }
+}
+
+// 定石か対局と同じ think の手 show_analysis なら全ての手を評価して評価値と読み筋を添える
+// The book move or the move think() plays; with show_analysis every move is also scored to
+// show the played move's score and principal variation
+fn think(
+    engine: &Engine,
+    first: u64,
+    second: u64,
+    left: usize,
+    show_analysis: bool,
+) -> (Pmove, String) {
+    if let Some(mv) = engine.book_move(first, second) {
+        return (Pmove::Pvmove(mv), format!("{} 定石", make_index(mv)));
+    }
+    let mv = match legal_move(engine.think(first, second, left), first, second) {
+        Pmove::Pvmove(mv) => mv,
+        _ => return (Pmove::Passmove, "パス".to_string()),
+    };
+    let mut text = make_index(mv);
+    if show_analysis {
+        let analysis = engine.analyze(first, second, left);
+        if let Some((_, score)) = analysis.moves.iter().find(|(scored, _)| *scored == mv) {
+            text.push_str(&format!(" 評価 {}", score));
+        }
+        // 読み筋は最善手のもの  The principal variation follows the best analysed move
+        if analysis.moves.first().is_some_and(|&(best, _)| best == mv) {
+            text.push_str(&format!(" 読み筋 {}", analysis.pv_text()));
+        }
+        text.push_str(&format!(" ノード {}", analysis.nodes));
+        if let Some(bytes) = analysis.memory_per_simulation() {
+            text.push_str(&format!(" {:.0}B/sim", bytes));
+        }
+    }
+    (Pmove::Pvmove(mv), text)
+}
+
+fn square_bit(column: u16, row: u16) -> u64 {
+    0x8000_0000_0000_0000 >> (column + row * 8)
+}
+
+// 画面の座標のマス  The square under a screen position
+fn square_at(x: u16, y: u16) -> Option<(u16, u16)> {
+    if x < BOARD_X || y < BOARD_Y {
+        return None;
+    }
+    let (column, row) = ((x - BOARD_X) / CELL_WIDTH, y - BOARD_Y);
+    (column < 8 && row < 8).then_some((column, row))
+}
+
+// 最後の着手で返った石  Discs flipped by the last move
+fn flipped_discs(board: &Board) -> u64 {
+    match board.history.last() {
+        Some(record) if matches!(record.pmove, Pmove::Pvmove(_)) => match record.turn_player {
+            TurnPlayer::Black => record.white & board.black,
+            TurnPlayer::White => record.black & board.white,
+        },
+        _ => 0,
+    }
+}
+
+fn color_name(color: &TurnPlayer) -> &'static str {
+    match color {
+        TurnPlayer::Black => "黒",
+        TurnPlayer::White => "白",
+    }
+}
+
+fn result_text(board: &Board) -> String {
+    let (black, white) = (board.black.count_ones(), board.white.count_ones());
+    match black.cmp(&white) {
+        std::cmp::Ordering::Greater => format!("黒の勝ちです {}-{}", black, white),
+        std::cmp::Ordering::Less => format!("白の勝ちです {}-{}", black, white),
+        std::cmp::Ordering::Equal => format!("引き分けです {}-{}", black, white),
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bit_lib::parse_move;
+
+    #[test]
+    fn test_square_at() {
+        assert_eq!(square_at(BOARD_X, BOARD_Y), Some((0, 0)));
+        assert_eq!(square_at(BOARD_X + 5 * CELL_WIDTH + 2, BOARD_Y + 4), Some((5, 4)));
+        assert_eq!(square_bit(5, 4), parse_move("f5").unwrap());
+        assert_eq!(square_at(BOARD_X + 8 * CELL_WIDTH, BOARD_Y), None);
+        assert_eq!(square_at(0, BOARD_Y), None);
+    }
+
+    #[test]
+    fn test_flipped_discs() {
+        let start = Position::default();
+        let mut board = Board::new(
+            (start.turn_player, "X".to_string(), "O".to_string()),
+            start.black,
+            start.white,
+        );
+        assert_eq!(flipped_discs(&board), 0);
+        board.update_board(Pmove::Pvmove(parse_move("f5").unwrap()));
+        assert_eq!(flipped_discs(&board), parse_move("e5").unwrap());
+    }
+
+    #[test]
+    fn test_think() {
+        // main.rs の Test 局面 黒10石勝ち  The Test position is a 10-disc win for Black
+        let (black, white) = (0x6000_1008_1012_0500, 0x8EFC_EFF7_6F6D_3A3F);
+        let engine = Engine::new(crate::engine::Algorithm::AlphaBeta);
+        let (pmove, text) = think(&engine, black, white, 10, false);
+        assert_eq!(pmove, engine.think(black, white, 10));
+        assert_eq!(text, "D1");
+        let (_, text) = think(&engine, black, white, 10, true);
+        assert!(text.starts_with("D1 評価 +10 読み筋 D1 "));
+    }
+
+    #[test]
+    fn test_clock() {
+        let time_control = TimeControl::parse("60").ok();
+        let mut tui = Tui::new(Player::Human, Player::Human, Position::default(), time_control);
+        tui.apply(Pmove::Pvmove(parse_move("f5").unwrap()), Duration::from_secs(10));
+        let black = tui.clock(&TurnPlayer::Black);
+        assert_eq!(black, Duration::from_secs(50));
+        assert!(tui.clock(&TurnPlayer::White) <= Duration::from_secs(60));
+        // 残り時間を超えると時間切れ負け  Using more than the time left loses on time
+        tui.apply(Pmove::Pvmove(parse_move("d6").unwrap()), Duration::from_secs(61));
+        assert_eq!(tui.flagged, Some(TurnPlayer::White));
+        assert_eq!(tui.board.history.len(), 1);
+        assert!(tui.is_over());
+        let tui = Tui::new(Player::Human, Player::Human, Position::default(), None);
+        assert!(tui.clock(&TurnPlayer::White).is_zero());
+    }
+}
@@ -0,0 +1,155 @@
+// WebAssembly bindings for Othello
+// ブラウザでは Instant が使えないので、エンジンは depth と playouts だけで止める
+// Instant is not available in the browser, so the engines stop by depth and playouts only
+
+use wasm_bindgen::prelude::*;
+
+// from  bit_lib
+use crate::bit_lib::{can_locate, make_index, make_legal_actions, parse_move};
+use crate::board::Board;
+use crate::engine::{Algorithm, Engine};
+use crate::position::{format_squares, Position};
+use crate::{Pmove, TurnPlayer}; // from lib
+
+#[wasm_bindgen]
+pub struct Game {
+    board: Board,
+}
+
+#[wasm_bindgen]
+impl Game {
+    // 初期局面  The initial position
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Game {
+        Game::from(Position::default())
+    }
+
+    // 開始局面と同じ形式の局面から  From a position in the --position forms
+    pub fn from_position(text: &str) -> Result<Game, JsError> {
+        Position::parse(text)
+            .map(Game::from)
+            .map_err(|e| JsError::new(&e))
+    }
+
+    // "F5" のような合法手 打てなければ空  Legal moves like "F5", empty when the side must pass
+    pub fn legal_moves(&self) -> Vec<String> {
+        make_legal_actions(self.board.first, self.board.second)
+            .into_iter()
+            .map(make_index)
+            .collect()
+    }
+
+    // "f5" または "pass"  A move such as "f5", or "pass" when there is no legal move
+    pub fn play(&mut self, mv: &str) -> Result<(), JsError> {
+        let bit = parse_move(mv).ok_or_else(|| JsError::new(&format!("bad move: {}", mv)))?;
+        let legal = can_locate(self.board.first, self.board.second);
+        if !self.board.is_can_play() || (bit == 0 && legal != 0) || (bit != 0 && legal & bit == 0)
+        {
+            return Err(JsError::new(&format!("illegal move: {}", mv)));
+        }
+        self.board.update_board(if bit == 0 { Pmove::Passmove } else { Pmove::Pvmove(bit) });
+        Ok(())
+    }
+
+    // 一手戻す  Take back one move, false when there is none
+    pub fn undo(&mut self) -> bool {
+        self.board.undo().is_some()
+    }
+
+    // "black" または "white"  The side to move
+    pub fn to_move(&self) -> String {
+        match self.board.turn_player {
+            TurnPlayer::Black => "black".to_string(),
+            TurnPlayer::White => "white".to_string(),
+        }
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        !self.board.is_can_play()
+    }
+
+    pub fn black_count(&self) -> u32 {
+        self.board.black.count_ones()
+    }
+
+    pub fn white_count(&self) -> u32 {
+        self.board.white.count_ones()
+    }
+
+    // A1 から H8 の64文字 'X' 黒 'O' 白 '-' 空き  64 squares from A1 to H8
+    pub fn squares(&self) -> String {
+        format_squares(self.board.black, self.board.white, 'X', 'O')
+    }
+
+    // alphabeta の着手 打てなければ "PA"  The alphabeta move, "PA" for a pass
+    pub fn alphabeta_move(&self, depth: i32) -> String {
+        let mut engine = Engine::new(Algorithm::AlphaBeta);
+        engine.limits.depth = depth.max(1);
+        self.think(&engine)
+    }
+
+    // MCTS の着手 打てなければ "PA"  The MCTS move, "PA" for a pass
+    pub fn mcts_move(&self, playouts: u32) -> String {
+        let mut engine = Engine::new(Algorithm::Mcts);
+        engine.limits.playouts = playouts.max(1);
+        self.think(&engine)
+    }
+}
+
+impl Game {
+    fn think(&self, engine: &Engine) -> String {
+        match engine.think(self.board.first, self.board.second, self.board.left) {
+            Pmove::Pvmove(mv) => make_index(mv),
+            _ => "PA".to_string(),
+        }
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new()
+    }
+}
+
+impl From<Position> for Game {
+    fn from(position: Position) -> Self {
+        let board = Board::new(
+            (position.turn_player, "Black".to_string(), "White".to_string()),
+            position.black,
+            position.white,
+        );
+        Game { board }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_game() {
+        let mut game = Game::new();
+        assert_eq!(game.legal_moves(), vec!["E6", "F5", "C4", "D3"]);
+        assert!(game.play("f5").is_ok());
+        assert!(game.play("f5").is_err());
+        assert_eq!(game.to_move(), "white");
+        assert_eq!((game.black_count(), game.white_count()), (4, 1));
+        assert!(["D6", "F4", "F6"].contains(&game.alphabeta_move(4).as_str()));
+        assert!(["D6", "F4", "F6"].contains(&game.mcts_move(200).as_str()));
+        assert!(game.undo());
+        assert_eq!(game.squares(), Position::default().to_string()[..64]);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_game_pass_and_end() {
+        let mut game = Game::from_position("0x8000000000000000 0x4000000000000000 w").unwrap();
+        assert!(game.legal_moves().is_empty());
+        assert!(game.play("c1").is_err());
+        assert!(game.play("pass").is_ok());
+        assert_eq!(game.alphabeta_move(2), "C1");
+        assert!(game.play("c1").is_ok());
+        assert!(game.is_game_over());
+        assert_eq!(game.mcts_move(10), "PA");
+    }
+}